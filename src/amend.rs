@@ -0,0 +1,122 @@
+use crate::menu::Menu;
+use crate::order::{OrderLine, SoldFood};
+
+/// 注文の修正の記録（修正前と修正後の行）
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct Amendment {
+    pub time: chrono::DateTime<chrono::Utc>,
+    pub before: Vec<OrderLine>,
+    pub after: Vec<OrderLine>,
+}
+
+/// 注文を修正するウィンドウの入力
+pub struct AmendForm {
+    // 修正する注文の `history` 上の位置
+    pub order: usize,
+    pub before: Vec<OrderLine>,
+    pub lines: Vec<OrderLine>,
+}
+
+impl AmendForm {
+    pub fn new(order: usize, lines: Vec<OrderLine>) -> Self {
+        Self {
+            order,
+            before: lines.clone(),
+            lines,
+        }
+    }
+
+    /// 修正するボタンが押されたら true
+    /// 味か個数を変えた行は、今のメニューの値段で計算し直す
+    /// メニューから外れた味の行は値段を計算し直せないので、個数を変えられなくする
+    pub fn ui(&mut self, ui: &mut egui::Ui, menu: &Menu) -> bool {
+        let mut remove = None;
+        let mut changed = None;
+
+        egui::Grid::new("amend_grid")
+            .num_columns(4)
+            .striped(true)
+            .show(ui, |ui| {
+                for (i, line) in self.lines.iter_mut().enumerate() {
+                    let name = menu
+                        .get(line.food.id)
+                        .map_or(line.food.name.clone(), |item| item.name.clone());
+                    egui::ComboBox::from_id_salt(("amend_food", i))
+                        .selected_text(name)
+                        .show_ui(ui, |ui| {
+                            for item in menu.active_items() {
+                                if ui
+                                    .selectable_label(line.food.id == item.id, &item.name)
+                                    .clicked()
+                                {
+                                    line.food.id = item.id;
+                                    line.food.name = item.name.clone();
+                                    changed = Some(i);
+                                }
+                            }
+                        });
+                    // 個数を変えるなら、メニューにある味を選び直してもらう
+                    let priced = menu.get(line.food.id).is_some();
+                    if ui
+                        .add_enabled(
+                            priced,
+                            egui::DragValue::new(&mut line.n).range(1..=99).suffix("個"),
+                        )
+                        .on_disabled_hover_text("メニューに無い味は個数を変えられません")
+                        .changed()
+                    {
+                        changed = Some(i);
+                    }
+                    ui.label(format!("¥{}", line.price));
+                    if ui.small_button("×").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if let Some(i) = changed {
+            let line = &mut self.lines[i];
+            if let Some(item) = menu.get(line.food.id) {
+                line.price = item.price_for(line.n);
+            }
+        }
+        if let Some(i) = remove {
+            self.lines.remove(i);
+        }
+
+        if let Some(item) = menu.active_items().next() {
+            if ui.button("行を追加").clicked() {
+                let time = self
+                    .before
+                    .first()
+                    .map_or_else(chrono::Utc::now, |line| line.food.time);
+                self.lines.push(OrderLine {
+                    food: SoldFood {
+                        id: item.id,
+                        name: item.name.clone(),
+                        time,
+                    },
+                    n: 1,
+                    price: item.price_for(1),
+                });
+            }
+        }
+
+        ui.separator();
+
+        let before: u32 = self.before.iter().map(|line| line.price).sum();
+        let after: u32 = self.lines.iter().map(|line| line.price).sum();
+        ui.label(format!(
+            "合計 ¥{} → ¥{}（差額 {:+}円）",
+            before,
+            after,
+            after as i64 - before as i64
+        ));
+
+        ui.separator();
+
+        ui.add_enabled(!self.lines.is_empty(), egui::Button::new("修正する"))
+            .clicked()
+    }
+}
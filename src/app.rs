@@ -1,3 +1,39 @@
+use crate::amend::{AmendForm, Amendment};
+use crate::audit;
+use crate::cashup::{CashUpForm, SessionReport};
+use crate::chart::{self, ChartOptions};
+use crate::check;
+use crate::compare::{self, Dashboard};
+use crate::event_log::{self, Event, OrderRef};
+use crate::ledger;
+use crate::menu::Menu;
+use crate::order::{FoodOrder, Order, OrderLine, SoldFood};
+use crate::register::{Register, RegisterAction};
+use crate::report::Printout;
+use crate::session::{self, NewSessionForm, Session};
+use crate::settings::Settings;
+use crate::staff::{self, LoginAction, LoginForm};
+use crate::undo::{Command, UndoStack};
+use crate::void::VoidForm;
+
+// 画面と報告書の日本語のフォント
+pub(crate) const NOTO_SANS_JP: &[u8] = include_bytes!("../fonts/NotoSansJP-Regular.ttf");
+
+// 選んだデータフォルダを残しておくキー（ネイティブ版用）
+#[cfg(not(target_arch = "wasm32"))]
+const STORAGE_DATA_DIR: &str = "food_counter.data_dir";
+
+// ブラウザの保存領域のキー（web 版用）
+// 同じ場所のほかのアプリと重ならないように、アプリの名前を付ける
+#[cfg(target_arch = "wasm32")]
+const STORAGE_MENU: &str = "food_counter.menu";
+#[cfg(target_arch = "wasm32")]
+const STORAGE_SETTINGS: &str = "food_counter.settings";
+#[cfg(target_arch = "wasm32")]
+const STORAGE_HISTORY: &str = "food_counter.history";
+#[cfg(target_arch = "wasm32")]
+const STORAGE_AUDIT: &str = "food_counter.audit";
+
 /// We derive Deserialize/Serialize so we can persist app state on shutdown.
 #[derive(serde::Deserialize, serde::Serialize)]
 #[serde(default)] // if we add new fields, give them default values when deserializing old state
@@ -5,9 +41,6 @@ pub struct TemplateApp {
     // Example stuff:
     label: String,
 
-    #[serde(skip)]
-    sold_food: Vec<SoldFood>,
-
     // 注文個数の履歴
     #[serde(skip)]
     pub history: Vec<Order>,
@@ -15,66 +48,673 @@ pub struct TemplateApp {
     // 注文個数
     #[serde(skip)]
     pub n: usize,
-}
 
-#[derive(serde::Deserialize, serde::Serialize)]
-pub enum Order {
-    Food(SoldFood, usize),
-    Reset,
+    // 確定前の注文（味ごとの行）
+    #[serde(skip)]
+    pub cart: Vec<OrderLine>,
+
+    // 次の注文の支払い方法
+    #[serde(skip)]
+    pub payment_method: String,
+
+    // 会計中の注文（会計パネルを開いている間だけ Some）
+    #[serde(skip)]
+    register: Option<Register>,
+
+    // レジ締めの入力（レジ締めウィンドウを開いている間だけ Some）
+    #[serde(skip)]
+    cash_up: Option<CashUpForm>,
+
+    // 修正する注文の入力（ウィンドウを開いている間だけ Some）
+    #[serde(skip)]
+    amend_form: Option<AmendForm>,
+
+    // 無効にする注文の入力（ウィンドウを開いている間だけ Some）
+    #[serde(skip)]
+    void_form: Option<VoidForm>,
+
+    // 新しいセッションの入力（ウィンドウを開いている間だけ Some）
+    #[serde(skip)]
+    new_session: Option<NewSessionForm>,
+
+    // 表示するセッション（None なら今のセッション）
+    #[serde(skip)]
+    pub selected_session: Option<u32>,
+
+    // 販売するメニュー
+    #[serde(skip)]
+    pub menu: Menu,
+
+    #[serde(skip)]
+    pub settings: Settings,
+
+    // 編集中のメニュー（編集ウィンドウを開いている間だけ Some）
+    #[serde(skip)]
+    menu_draft: Option<Menu>,
+
+    #[serde(skip)]
+    menu_new_name: String,
+
+    // 取り消し・やり直しのできる操作
+    #[serde(skip)]
+    commands: UndoStack,
+
+    // 取り消した注文（最近の注文の一覧に出す）
+    #[serde(skip)]
+    undone_orders: Vec<FoodOrder>,
+
+    // 最近の注文の一覧を出す
+    #[serde(skip)]
+    show_recent: bool,
+    // 最近の注文の一覧で見ているページ（0が最新）
+    #[serde(skip)]
+    recent_page: usize,
+
+    // 中央パネルに出すグラフ
+    #[serde(skip)]
+    chart: ChartOptions,
+
+    // 今レジを担当している人（注文と操作の記録に残す。空なら担当なし）
+    #[serde(skip)]
+    pub staff: String,
+
+    // 担当の交代の入力（ウィンドウを開いている間だけ Some）
+    #[serde(skip)]
+    login_form: Option<LoginForm>,
+
+    // セッションの比較（ウィンドウを開いている間だけ Some）
+    #[serde(skip)]
+    compare: Option<Dashboard>,
+
+    // 比較に足す記録ファイルの中身（選ばれたら入る）
+    #[serde(skip)]
+    archive_inbox: ledger::Inbox,
+
+    // 担当ごとの売上のウィンドウを出す
+    #[serde(skip)]
+    show_staff_report: bool,
+
+    // 記録を変える操作の記録
+    #[serde(skip)]
+    pub audit: Vec<audit::Entry>,
+
+    // 操作の記録のウィンドウを出す
+    #[serde(skip)]
+    show_audit: bool,
+
+    // データを置くフォルダ（空なら今のフォルダ）
+    #[serde(skip)]
+    pub data_dir: std::path::PathBuf,
+
+    // ブラウザにまだ保存していない変更がある（web 版用）
+    #[serde(skip)]
+    unsaved: bool,
+
+    // 読み込む記録ファイルの中身（選ばれたら入る）
+    #[serde(skip)]
+    import_inbox: ledger::Inbox,
+
+    // 読み込んだ記録で見つかった食い違い（確認ウィンドウに出す）
+    #[serde(skip)]
+    load_problems: Vec<String>,
+
+    // ブラウザの保存領域に保存できなかったとき、その理由（ウィンドウに出す）
+    #[serde(skip)]
+    storage_error: Option<String>,
 }
 
 impl Default for TemplateApp {
     fn default() -> Self {
+        let settings = Settings::default();
+        let session = Session {
+            id: 1,
+            name: NewSessionForm::default().name,
+            start: Some(chrono::Utc::now()),
+            float: 0,
+        };
+
         Self {
             // Example stuff:
             label: "Hello World!".to_owned(),
-            sold_food: vec![],
-            history: vec![],
+            history: vec![Order::Session(session)],
             n: 3,
+            cart: vec![],
+            payment_method: settings.payment_methods[0].name.clone(),
+            register: None,
+            cash_up: None,
+            amend_form: None,
+            void_form: None,
+            new_session: None,
+            selected_session: None,
+            menu: Menu::default(),
+            settings,
+            menu_draft: None,
+            menu_new_name: String::new(),
+            commands: UndoStack::default(),
+            undone_orders: vec![],
+            show_recent: true,
+            recent_page: 0,
+            chart: ChartOptions::default(),
+            staff: String::new(),
+            login_form: None,
+            compare: None,
+            archive_inbox: ledger::Inbox::default(),
+            show_staff_report: false,
+            audit: vec![],
+            show_audit: false,
+            data_dir: std::path::PathBuf::new(),
+            unsaved: false,
+            import_inbox: ledger::Inbox::default(),
+            load_problems: vec![],
+            storage_error: None,
         }
     }
 }
 
-#[derive(serde::Deserialize, serde::Serialize, Clone)]
-pub struct SoldFood {
-    pub name: String,
-    pub time: chrono::DateTime<chrono::Utc>,
-}
-
 impl TemplateApp {
-    pub fn add_sold_food(&mut self, name: String) {
-        for _ in 0..self.n {
-            self.sold_food.push(SoldFood {
-                name: name.clone(),
-                time: chrono::Utc::now(),
+    // 今の個数をカートに入れる（同じ味はまとめる）
+    pub fn add_to_cart(&mut self, id: u32) {
+        let Some(item) = self.menu.get(id) else {
+            return;
+        };
+        if self.n == 0 {
+            return;
+        }
+
+        if let Some(line) = self.cart.iter_mut().find(|line| line.food.id == id) {
+            line.n += self.n;
+            line.price = item.price_for(line.n);
+        } else {
+            self.cart.push(OrderLine {
+                food: SoldFood {
+                    id,
+                    name: item.name.clone(),
+                    time: chrono::Utc::now(),
+                },
+                n: self.n,
+                price: item.price_for(self.n),
             });
         }
+    }
 
-        self.history.push(Order::Food(
-            SoldFood {
-                name,
-                time: chrono::Utc::now(),
-            },
-            self.n,
-        ));
+    // カートの中身を1件の注文として確定する
+    pub fn add_sold_food(&mut self, mut lines: Vec<OrderLine>) {
+        if lines.is_empty() {
+            return;
+        }
+
+        let time = chrono::Utc::now();
+        for line in &mut lines {
+            line.food.time = time;
+        }
+        let mut order = FoodOrder::new(time, lines);
+        order.payment_method = Some(self.payment_method.clone());
+        order.session = self.current_session_id();
+        order.staff = (!self.staff.is_empty()).then(|| self.staff.clone());
+
+        self.push(Order::Food(order));
+    }
+
+    // 注文・レジ締め・セッションの開始を記録して、取り消せるようにする
+    fn push(&mut self, order: Order) {
+        self.record(Event::Push(order.clone()));
+        self.commands.push(Command::Push(order));
+    }
+
+    // 続けて何件か記録して、1回で取り消せるようにする
+    fn push_all(&mut self, orders: Vec<Order>) {
+        for order in &orders {
+            self.record(Event::Push(order.clone()));
+        }
+        self.commands.push(Command::PushAll(orders));
+    }
+
+    // 最後の記録を取り消して、取り消した記録を返す
+    fn pop(&mut self) -> Option<Order> {
+        let order = self.history.last().cloned()?;
+        match &order {
+            // 前のセッションに戻る
+            Order::Session(_) => self.selected_session = None,
+            Order::Food(food) => self.undone_orders.push(food.clone()),
+            Order::CashUp(_) => {}
+        }
+        self.register = None;
+        self.amend_form = None;
+        self.void_form = None;
+        self.record(Event::Pop);
+
+        Some(order)
+    }
+
+    // 取り消した記録を、もう一度記録する
+    fn repush(&mut self, order: &Order) {
+        if let Order::Food(food) = order {
+            self.undone_orders.retain(|undone| undone.time != food.time);
+        }
+        self.record(Event::Push(order.clone()));
+    }
+
+    // 注文個数を変える
+    pub fn set_quantity(&mut self, n: usize) {
+        if n == self.n {
+            return;
+        }
+        self.commands.push(Command::Quantity {
+            before: self.n,
+            after: n,
+        });
+        self.n = n;
+    }
+
+    // 最後の操作を取り消す
+    // 取り消せる操作が無ければ、以前のように最後の記録を取り消す
+    pub fn undo(&mut self) {
+        let command = self.commands.take_undo().or_else(|| {
+            // 最初のセッションは取り消さない
+            (self.history.len() > 1)
+                .then(|| self.history.last().cloned().map(Command::Push))
+                .flatten()
+        });
+
+        match command {
+            Some(Command::Push(_)) => {
+                let Some(order) = self.pop() else {
+                    return;
+                };
+                self.audit(audit::Action::Undo {
+                    what: self.order_description(&order),
+                });
+                // 会計の結果も含めてやり直せるように、取り消す直前の記録を残す
+                self.commands.undone(Command::Push(order));
+            }
+            Some(Command::PushAll(orders)) => {
+                // 後ろから取り消して、足したときの順に戻す
+                let mut undone: Vec<Order> = (0..orders.len()).map_while(|_| self.pop()).collect();
+                undone.reverse();
+                self.audit(audit::Action::Undo {
+                    what: self.orders_description(&undone),
+                });
+                self.commands.undone(Command::PushAll(undone));
+            }
+            Some(Command::Quantity { before, after }) => {
+                self.n = before;
+                self.commands.undone(Command::Quantity { before, after });
+            }
+            Some(Command::Menu { before, after }) => {
+                self.set_menu(before.clone());
+                self.audit(audit::Action::Undo {
+                    what: "メニューの変更".to_owned(),
+                });
+                self.commands.undone(Command::Menu { before, after });
+            }
+            None => {}
+        }
+    }
+
+    // 取り消した操作をやり直す
+    pub fn redo(&mut self) {
+        match self.commands.take_redo() {
+            Some(Command::Push(order)) => {
+                self.repush(&order);
+                self.audit(audit::Action::Redo {
+                    what: self.order_description(&order),
+                });
+                self.commands.redone(Command::Push(order));
+            }
+            Some(Command::PushAll(orders)) => {
+                for order in &orders {
+                    self.repush(order);
+                }
+                self.selected_session = None;
+                self.audit(audit::Action::Redo {
+                    what: self.orders_description(&orders),
+                });
+                self.commands.redone(Command::PushAll(orders));
+            }
+            Some(Command::Quantity { before, after }) => {
+                self.n = after;
+                self.commands.redone(Command::Quantity { before, after });
+            }
+            Some(Command::Menu { before, after }) => {
+                self.set_menu(after.clone());
+                self.audit(audit::Action::Redo {
+                    what: "メニューの変更".to_owned(),
+                });
+                self.commands.redone(Command::Menu { before, after });
+            }
+            None => {}
+        }
+    }
+
+    // 取り消し・やり直しの記録に出す説明
+    fn order_description(&self, order: &Order) -> String {
+        match order {
+            Order::Food(order) => format!(
+                "注文 {} {}",
+                order.time.with_timezone(&chrono::Local).format("%H:%M:%S"),
+                self.lines_summary(&order.lines)
+            ),
+            Order::CashUp(_) => "レジ締め".to_owned(),
+            Order::Session(session) => format!("セッション {}", session.name),
+        }
+    }
+
+    fn orders_description(&self, orders: &[Order]) -> String {
+        orders
+            .iter()
+            .map(|order| self.order_description(order))
+            .collect::<Vec<_>>()
+            .join("・")
+    }
+
+    // 記録を変える操作を、誰がいつしたか残す
+    fn audit(&mut self, action: audit::Action) {
+        let entry = audit::Entry {
+            time: chrono::Utc::now(),
+            staff: self.staff.clone(),
+            action,
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(e) = event_log::append(&self.data_path("audit.jsonl"), &entry) {
+            eprintln!("Failed to save file: {}", e);
+        }
+
+        self.audit.push(entry);
+        self.unsaved = true;
+    }
+
+    // 記録をログに書いてから `history` に反映する
+    fn record(&mut self, event: Event) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(e) = event_log::append(&self.data_path("events.jsonl"), &event) {
+            eprintln!("Failed to save file: {}", e);
+        }
+
+        event.apply(&mut self.history);
+        self.unsaved = true;
+    }
+
+    // 表示しているセッションで、味ごとに売れた個数を返す
+    pub fn sold_food_count(&self) -> Vec<(u32, String, usize)> {
+        self.sold_food_count_in(self.shown_session_id())
+    }
+
+    // 味ごとに、メニューの番号・名前と売れた個数を返す
+    // メニューの順に並べ、メニューから外れた名前の記録は末尾に追加する（番号の無い古い記録は 0）
+    // 名前を変えたメニューは番号で数えるので、古い記録も新しい名前で数える
+    pub fn sold_food_count_in(&self, session: u32) -> Vec<(u32, String, usize)> {
+        let mut count: Vec<(u32, String, usize)> = self
+            .menu
+            .items
+            .iter()
+            .map(|item| (item.id, item.name.clone(), 0))
+            .collect();
+
+        for food in self
+            .session_orders(session)
+            .flat_map(|order| order.sold_food())
+        {
+            let found = count.iter_mut().find(|(id, name, _)| {
+                if food.id != 0 {
+                    *id == food.id
+                } else {
+                    *name == food.name
+                }
+            });
+            if let Some((_, _, n)) = found {
+                *n += 1;
+            } else {
+                count.push((food.id, food.name.clone(), 1));
+            }
+        }
+
+        // 販売終了して一度も売れていないものは出さない
+        count
+            .into_iter()
+            .filter(|(id, _, n)| *n > 0 || self.menu.get(*id).map_or(true, |item| !item.retired))
+            .collect()
+    }
+
+    // セッションの売上
+    pub fn session_revenue(&self, session: u32) -> u32 {
+        self.session_orders(session)
+            .map(|order| order.total())
+            .sum()
+    }
+
+    // セッションの売上を支払い方法ごとに返す
+    pub fn session_revenue_by_method(&self, session: u32) -> Vec<(String, u32)> {
+        self.revenue_by_method(self.session_orders(session))
+    }
+
+    // 支払い方法ごとの売上
+    // 設定の順に並べ、設定に無い方法や未記録は末尾に追加する
+    fn revenue_by_method<'a>(
+        &self,
+        orders: impl Iterator<Item = &'a FoodOrder>,
+    ) -> Vec<(String, u32)> {
+        let mut totals: Vec<(String, u32)> = self
+            .settings
+            .payment_methods
+            .iter()
+            .map(|method| (method.name.clone(), 0))
+            .collect();
+
+        for order in orders {
+            let name = order.payment_method.as_deref().unwrap_or("未記録");
+            if let Some((_, total)) = totals.iter_mut().find(|(n, _)| n == name) {
+                *total += order.total();
+            } else {
+                totals.push((name.to_owned(), order.total()));
+            }
+        }
+
+        totals
+    }
+
+    // セッションに入っている注文（無効にした注文は除く）
+    pub fn session_orders(&self, session: u32) -> impl Iterator<Item = &FoodOrder> {
+        self.history.iter().filter_map(move |order| match order {
+            Order::Food(order) if order.session == session && !order.is_void() => Some(order),
+            _ => None,
+        })
+    }
+
+    // 今のセッション（`history` の最後のセッション）
+    // 読み込んだ直後などでセッションが1つも無ければ None
+    pub fn current_session(&self) -> Option<&Session> {
+        self.history.iter().rev().find_map(|order| match order {
+            Order::Session(session) => Some(session),
+            _ => None,
+        })
+    }
+
+    // 今のセッションの番号（セッションが無ければ、セッションの無い注文と同じ 0）
+    pub fn current_session_id(&self) -> u32 {
+        self.current_session().map_or(0, |session| session.id)
+    }
+
+    // 表示しているセッション
+    pub fn shown_session(&self) -> Option<&Session> {
+        self.selected_session
+            .and_then(|id| {
+                self.history.iter().find_map(|order| match order {
+                    Order::Session(session) if session.id == id => Some(session),
+                    _ => None,
+                })
+            })
+            .or_else(|| self.current_session())
+    }
+
+    pub fn shown_session_id(&self) -> u32 {
+        self.shown_session().map_or(0, |session| session.id)
+    }
+
+    // 今のセッションの現金売上
+    // 支払い方法が記録されていない古い注文は現金として数える
+    pub fn session_cash_sales(&self) -> u32 {
+        self.session_orders(self.current_session_id())
+            .filter(|order| match &order.payment_method {
+                Some(name) => self
+                    .settings
+                    .payment_method(name)
+                    .is_some_and(|method| method.cash),
+                None => true,
+            })
+            .map(|order| order.total())
+            .sum()
+    }
+
+    // レジ締めをして、集計を保存してから新しいセッションを始める
+    pub fn close_session(&mut self, form: &CashUpForm) {
+        let Some(session) = self.current_session().cloned() else {
+            return;
+        };
+        let cash_up = form.cash_up(self.session_cash_sales());
+        let report = SessionReport {
+            orders: self.session_orders(session.id).count(),
+            sold: self
+                .session_orders(session.id)
+                .map(|order| order.count())
+                .sum(),
+            revenue: self.session_revenue(session.id),
+            revenue_by_method: self.session_revenue_by_method(session.id),
+            sold_food_count: self
+                .sold_food_count_in(session.id)
+                .into_iter()
+                .map(|(_, name, n)| (name, n))
+                .collect(),
+            difference: cash_up.difference(),
+            by_staff: staff::totals(self.session_orders(session.id)),
+            shifts: staff::shifts(self.session_orders(session.id)),
+            cash_up: cash_up.clone(),
+            session,
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(e) = report.save_to_dir(&self.data_path("reports")) {
+            eprintln!("Failed to save file: {}", e);
+        }
+
+        self.audit(audit::Action::CashUp {
+            session: report.session.name.clone(),
+            difference: report.difference,
+        });
+        // レジ締めと次のセッションは1回で取り消す
+        self.open_session(vec![Order::CashUp(cash_up)], &form.next);
+    }
+
+    // 新しいセッションを始める
+    pub fn start_session(&mut self, form: &NewSessionForm) {
+        self.open_session(vec![], form);
+    }
+
+    // `before` の記録に続けて、新しいセッションを始める
+    fn open_session(&mut self, mut before: Vec<Order>, form: &NewSessionForm) {
+        before.push(Order::Session(Session {
+            id: session::next_id(&self.history),
+            name: form.name.clone(),
+            start: Some(chrono::Utc::now()),
+            float: form.float,
+        }));
+        if before.len() == 1 {
+            self.push(before.remove(0));
+        } else {
+            self.push_all(before);
+        }
+        self.selected_session = None;
+        self.audit(audit::Action::NewSession {
+            name: form.name.clone(),
+        });
+
+        // セッションの区切りごとにログを残しておく
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(e) = crate::file::backup(&self.data_path("events.jsonl")) {
+            eprintln!("Failed to save file: {}", e);
+        }
+    }
+
+    // 選んでいる支払い方法が設定に無ければ、先頭のものにする
+    fn select_default_payment_method(&mut self) {
+        if self.settings.payment_method(&self.payment_method).is_none() {
+            if let Some(method) = self.settings.payment_methods.first() {
+                self.payment_method = method.name.clone();
+            }
+        }
+    }
+
+    // 番号の無い古い記録を、名前からメニューの番号に紐付ける
+    fn attach_item_ids(&mut self) {
+        let menu = &self.menu;
+        let attach = |food: &mut SoldFood| {
+            if food.id == 0 {
+                if let Some(item) = menu.find_by_name(&food.name) {
+                    food.id = item.id;
+                }
+            }
+        };
+
+        for order in &mut self.history {
+            if let Order::Food(order) = order {
+                for line in &mut order.lines {
+                    attach(&mut line.food);
+                }
+            }
+        }
+    }
+
+    // 編集したメニューを反映して保存する
+    pub fn apply_menu(&mut self, menu: Menu) {
+        if menu == self.menu {
+            return;
+        }
+        self.commands.push(Command::Menu {
+            before: self.menu.clone(),
+            after: menu.clone(),
+        });
+        self.audit(audit::Action::Menu {
+            what: self.menu.changes(&menu),
+        });
+        self.set_menu(menu);
+    }
+
+    fn set_menu(&mut self, menu: Menu) {
+        self.menu = menu;
+        self.unsaved = true;
 
         self.save_to_file()
             .unwrap_or_else(|e| eprintln!("Failed to save file: {}", e));
     }
 
-    // 名前ごとに売れた個数を返す
-    pub fn sold_food_count(&self) -> Vec<(String, usize)> {
-        // プレーン、チョコ、いちご、はちみつ、シナモン
-        let mut count = vec![];
+    // 担当を交代する（空なら担当なし）
+    pub fn log_in(&mut self, name: &str) {
+        self.staff = name.to_owned();
+        self.audit(audit::Action::Login {
+            name: name.to_owned(),
+        });
+    }
 
-        for food in &["プレーン", "チョコ", "いちご", "はちみつ", "シナモン"] {
-            count.push((
-                food.to_string(),
-                self.sold_food.iter().filter(|f| f.name == *food).count(),
-            ));
+    // 設定を変えたら保存する
+    fn save_settings(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(e) = self.settings.save_to_file(&self.data_path("settings.json")) {
+            eprintln!("Failed to save file: {}", e);
         }
 
-        count
+        self.unsaved = true;
+    }
+
+    // 会計の結果を注文に記録する
+    pub fn record_payment(&mut self, index: usize, tendered: u32, change: u32) {
+        let Some(Order::Food(order)) = self.history.get(index) else {
+            return;
+        };
+        self.record(Event::Paid {
+            order: OrderRef::Time(order.time),
+            tendered,
+            change,
+        });
     }
 
     /// Called once before the first frame.
@@ -92,7 +732,7 @@ impl TemplateApp {
 
         fonts.font_data.insert(
             "NotoSansJP".to_owned(),
-            egui::FontData::from_static(include_bytes!("../fonts/NotoSansJP-Regular.ttf")),
+            egui::FontData::from_static(NOTO_SANS_JP),
         );
 
         fonts
@@ -105,6 +745,18 @@ impl TemplateApp {
 
         // load
         let mut app = Self::default();
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let saved = cc
+                .storage
+                .and_then(|storage| storage.get_string(STORAGE_DATA_DIR))
+                .map(std::path::PathBuf::from);
+            app.data_dir = crate::data_dir::resolve(saved);
+        }
+        #[cfg(target_arch = "wasm32")]
+        if let Some(storage) = cc.storage {
+            app.load_from_storage(storage);
+        }
 
         app.load_from_file()
             .unwrap_or_else(|e| eprintln!("Failed to load file: {}", e));
@@ -113,6 +765,7 @@ impl TemplateApp {
     }
 
     pub fn save_to_file(&self) -> Result<(), Box<dyn std::error::Error>> {
+        // web 版は `save_to_storage` でブラウザに保存する
         #[cfg(target_arch = "wasm32")]
         {
             return Ok(());
@@ -120,14 +773,9 @@ impl TemplateApp {
 
         #[cfg(not(target_arch = "wasm32"))]
         {
-            // 保存
-            let path = "sold_food.json";
-            if let Err(e) = self.save_to_file_sold_food(std::path::Path::new(path)) {
-                eprintln!("Failed to save file: {}", e);
-            }
-            // 注文個数の履歴を保存
-            let path = "history.json";
-            if let Err(e) = self.save_to_file_history(std::path::Path::new(path)) {
+            // メニュー
+            // 注文の記録は `record` で1件ずつ `events.jsonl` に追記している
+            if let Err(e) = self.menu.save_to_file(&self.data_path("menu.json")) {
                 eprintln!("Failed to save file: {}", e);
             }
         }
@@ -135,66 +783,500 @@ impl TemplateApp {
         Ok(())
     }
 
-    pub fn save_to_file_sold_food(
-        &self,
-        path: &std::path::Path,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        if std::fs::exists(path)? {
-            std::fs::remove_file(path)?;
+    pub fn load_from_file(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut problems = vec![];
+
+        // 以前の `history.json` から作り直したか
+        #[cfg(not(target_arch = "wasm32"))]
+        let from_legacy = {
+            std::fs::create_dir_all(&self.data_dir)?;
+
+            // メニュー
+            match Menu::load_from_file(&self.data_path("menu.json")) {
+                Ok(menu) => self.menu = menu,
+                Err(e) => eprintln!("Failed to load file: {}", e),
+            }
+            // 設定
+            match Settings::load_from_file(&self.data_path("settings.json")) {
+                Ok(settings) => self.settings = settings,
+                Err(e) => eprintln!("Failed to load file: {}", e),
+            }
+            // 操作の記録
+            match audit::load(&self.data_path("audit.jsonl")) {
+                Ok(audit) => self.audit = audit,
+                Err(e) => eprintln!("Failed to load file: {}", e),
+            }
+            // 注文の記録
+            let path = &self.data_path("events.jsonl");
+            if std::fs::exists(path)? {
+                // 壊れた行があれば、読めた記録で直す
+                let (history, repaired) = event_log::load(path)?;
+                self.history = history;
+                problems.extend(repaired);
+                false
+            } else {
+                // ログが無ければ、以前の `history.json` から作る
+                let legacy = &self.data_path("history.json");
+                crate::file::backup(legacy)?;
+                if let Err(e) = self.load_history_from_file(legacy) {
+                    eprintln!("Failed to load file: {}", e);
+                }
+                true
+            }
+        };
+
+        self.attach_item_ids();
+        session::normalize_sessions(&mut self.history);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let path = &self.data_path("events.jsonl");
+            if from_legacy {
+                event_log::write_all(path, &self.history)?;
+            }
+            // 起動したときの状態を残しておく
+            crate::file::backup(path)?;
+            // ログを作ったあとに以前の版で動かしても `sold_food.json` ができるので、毎回確かめる
+            problems.extend(self.retire_sold_food_file()?);
         }
-        let file = std::fs::File::create(path)?;
-        serde_json::to_writer(file, self.sold_food.as_slice())?;
+
+        self.select_default_payment_method();
+        // 読み込み直した記録には、前の操作を当てはめられない
+        self.commands.clear();
+        self.undone_orders.clear();
+
+        problems.extend(self.repair_history()?);
+        // 直しようがなく知らせるだけのものは、前に知らせていれば出さない
+        let reported = check::check_change(&self.history);
+        problems.extend(reported.into_iter().filter(|problem| {
+            !self.audit.iter().any(
+                |entry| matches!(&entry.action, audit::Action::Problem { what } if what == problem),
+            )
+        }));
+        for problem in &problems {
+            eprintln!("{}", problem);
+            self.audit(audit::Action::Problem {
+                what: problem.clone(),
+            });
+        }
+        self.load_problems = problems;
 
         Ok(())
     }
 
-    pub fn save_to_file_history(
-        &self,
-        path: &std::path::Path,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        if std::fs::exists(path)? {
-            std::fs::remove_file(path)?;
+    // 記録の食い違いを直し、直したらログを書き直す
+    fn repair_history(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let problems = check::repair_history(&mut self.history);
+        if !problems.is_empty() {
+            self.unsaved = true;
+
+            #[cfg(not(target_arch = "wasm32"))]
+            event_log::write_all(&self.data_path("events.jsonl"), &self.history)?;
         }
-        let file = std::fs::File::create(path)?;
-        serde_json::to_writer(file, self.history.as_slice())?;
 
-        Ok(())
+        Ok(problems)
     }
 
-    pub fn load_from_file(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        #[cfg(target_arch = "wasm32")]
+    // 以前の `sold_food.json` を履歴と比べてから片付ける
+    // 売れた玉は履歴から数えるので、ファイルは `backups/` に残して消す
+    #[cfg(not(target_arch = "wasm32"))]
+    fn retire_sold_food_file(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let path = &self.data_path("sold_food.json");
+        if !std::fs::exists(path)? {
+            return Ok(vec![]);
+        }
+
+        let problems = match serde_json::from_slice::<Vec<SoldFood>>(&std::fs::read(path)?) {
+            // リセットしてすぐに閉じていたら、その日のセッションをここで終える
+            Ok(sold_food) if check::end_reset_session(&sold_food, &mut self.history) => {
+                if let Some(session) = self.history.last() {
+                    event_log::append(
+                        &self.data_path("events.jsonl"),
+                        &Event::Push(session.clone()),
+                    )?;
+                }
+                vec!["sold_food.json が空なので、リセットしたあとに閉じたものとして新しいセッションを始めました".to_owned()]
+            }
+            Ok(sold_food) => check::compare_sold_food(
+                &sold_food,
+                self.session_orders(self.current_session_id())
+                    .flat_map(|order| order.sold_food()),
+            ),
+            Err(e) => vec![format!("sold_food.json を読めませんでした：{}", e)],
+        };
+
+        crate::file::backup(path)?;
+        std::fs::remove_file(path)?;
+
+        Ok(problems)
+    }
+
+    // 全部の記録を JSON と CSV で書き出す
+    pub fn export_ledger(&self, csv: bool) -> Result<(), Box<dyn std::error::Error>> {
+        let time = chrono::Local::now().format("%Y%m%d-%H%M%S");
+        if csv {
+            ledger::save_csv(
+                &format!("ledger-{}.csv", time),
+                &ledger::to_csv(&self.history),
+            )
+        } else {
+            ledger::save_as(
+                &format!("ledger-{}.json", time),
+                "application/json",
+                &crate::schema::to_string_pretty(&self.history)?,
+            )
+        }
+    }
+
+    // 表示しているセッションの味ごとの個数を書き出す
+    pub fn export_summary(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let time = chrono::Local::now().format("%Y%m%d-%H%M%S");
+        ledger::save_csv(
+            &format!("summary-{}.csv", time),
+            &ledger::summary_csv(
+                self.sold_food_count()
+                    .iter()
+                    .map(|(_, name, n)| (name.as_str(), *n)),
+            ),
+        )
+    }
+
+    // 表示しているセッションの報告書（印刷用の HTML）を書き出す
+    pub fn export_report(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let shown = self.shown_session_id();
+        let ranges = session::sessions(&self.history);
+        let range = ranges
+            .iter()
+            .find(|range| range.session.id == shown)
+            .ok_or("Session not found")?;
+        let records = &self.history[range.range.clone()];
+
+        let printout = Printout {
+            session: range.session,
+            end: range.end,
+            orders: self.session_orders(shown).count(),
+            sold: self.session_orders(shown).map(|order| order.count()).sum(),
+            revenue: self.session_revenue(shown),
+            flavors: chart::flavors(self.sold_food_count_in(shown), &self.menu),
+            revenue_by_method: self.session_revenue_by_method(shown),
+            hourly: chart::TimeSeries::new(self.session_orders(shown), &self.menu, 60, 24),
+            voids: records
+                .iter()
+                .filter_map(|order| match order {
+                    Order::Food(order) => order.void.as_ref().map(|void| {
+                        (
+                            order.time,
+                            self.lines_summary(&order.lines),
+                            void.reason.clone(),
+                        )
+                    }),
+                    _ => None,
+                })
+                .collect(),
+            cash_up: records.iter().rev().find_map(|order| match order {
+                Order::CashUp(cash_up) => Some(cash_up),
+                _ => None,
+            }),
+        };
+
+        let time = chrono::Local::now().format("%Y%m%d-%H%M%S");
+        ledger::save_as(
+            &format!("report-{}.html", time),
+            "text/html;charset=utf-8",
+            &printout.to_html(),
+        )
+    }
+
+    // 書き出した記録を読み込んで、今の記録に足す
+    // 足したあとはログを書き直す（前のログは `backups/` に残る）
+    pub fn import_ledger(&mut self, bytes: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let imported: Vec<Order> = crate::schema::from_slice(bytes)?;
+        self.audit(audit::Action::Import {
+            orders: imported.len(),
+        });
+        ledger::merge(&mut self.history, imported, &self.menu);
+        self.attach_item_ids();
+        session::normalize_sessions(&mut self.history);
+
+        // 並びが変わるので、会計中の注文の番号や取り消す操作は使えない
+        self.register = None;
+        self.amend_form = None;
+        self.void_form = None;
+        self.commands.clear();
+        self.selected_session = None;
+        self.unsaved = true;
+
+        #[cfg(not(target_arch = "wasm32"))]
         {
-            return Ok(());
+            let path = &self.data_path("events.jsonl");
+            crate::file::backup(path)?;
+            event_log::write_all(path, &self.history)?;
+        }
+
+        Ok(())
+    }
+
+    // ブラウザの保存領域（localStorage）に書き出す
+    #[cfg(target_arch = "wasm32")]
+    fn save_to_storage(&mut self, storage: &mut dyn eframe::Storage) {
+        let values = [
+            (STORAGE_MENU, crate::schema::to_string(&self.menu)),
+            (STORAGE_SETTINGS, crate::schema::to_string(&self.settings)),
+            (STORAGE_HISTORY, crate::schema::to_string(&self.history)),
+            (STORAGE_AUDIT, crate::schema::to_string(&self.audit)),
+        ];
+        for (key, value) in values {
+            match value {
+                Ok(value) => storage.set_string(key, value),
+                Err(e) => {
+                    log::error!("Failed to save {}: {}", key, e);
+                    self.storage_error = Some(format!("{}：{}", key, e));
+                }
+            }
+        }
+        storage.flush();
+        self.unsaved = false;
+    }
+
+    // ブラウザの保存領域から読み込む
+    // 続けて `load_from_file` を呼んで、読み込んだ履歴を整える
+    #[cfg(target_arch = "wasm32")]
+    fn load_from_storage(&mut self, storage: &dyn eframe::Storage) {
+        // 以前の形式やキーから読んだら true も返す
+        fn load<T: crate::schema::Versioned>(
+            storage: &dyn eframe::Storage,
+            key: &str,
+        ) -> Option<(T, bool)> {
+            // アプリの名前を付ける前は、名前の無いキーに保存していた
+            let (key, text, renamed) = match storage.get_string(key) {
+                Some(text) => (key, text, false),
+                None => {
+                    let key = key.trim_start_matches("food_counter.");
+                    (key, storage.get_string(key)?, true)
+                }
+            };
+            match crate::schema::from_slice(text.as_bytes()) {
+                Ok(value) => Some((value, renamed)),
+                // 形式の番号を付ける前は `eframe::set_value` で RON にして保存していた
+                Err(e) => match eframe::get_value(storage, key) {
+                    Some(value) => Some((value, true)),
+                    None => {
+                        log::error!("Failed to load {}: {}", key, e);
+                        None
+                    }
+                },
+            }
+        }
+
+        let mut migrated = false;
+        if let Some((menu, old)) = load(storage, STORAGE_MENU) {
+            self.menu = menu;
+            migrated |= old;
         }
+        if let Some((settings, old)) = load(storage, STORAGE_SETTINGS) {
+            self.settings = settings;
+            migrated |= old;
+        }
+        if let Some((history, old)) = load(storage, STORAGE_HISTORY) {
+            self.history = history;
+            migrated |= old;
+        }
+        if let Some((audit, old)) = load(storage, STORAGE_AUDIT) {
+            self.audit = audit;
+            migrated |= old;
+        }
+        // 次のフレームで今の形式にして保存し直す
+        if migrated {
+            self.unsaved = true;
+        }
+    }
+
+    // データフォルダの中のファイル
+    pub fn data_path(&self, name: &str) -> std::path::PathBuf {
+        self.data_dir.join(name)
+    }
+
+    // 別のデータフォルダに切り替えて読み込み直す
+    // 担当はそのまま引き継ぐ
+    pub fn open_data_dir(&mut self, data_dir: std::path::PathBuf) {
+        *self = Self {
+            data_dir,
+            staff: std::mem::take(&mut self.staff),
+            ..Self::default()
+        };
+        self.reload();
+    }
+
+    // 既にあるフォルダを選んで切り替え、次に起動したときも同じフォルダを使う
+    #[cfg(not(target_arch = "wasm32"))]
+    fn pick_data_dir(&mut self, frame: &mut eframe::Frame) {
+        let Some(data_dir) = rfd::FileDialog::new()
+            .set_directory(&self.data_dir)
+            .pick_folder()
+        else {
+            return;
+        };
+
+        if let Some(storage) = frame.storage_mut() {
+            storage.set_string(STORAGE_DATA_DIR, data_dir.display().to_string());
+            storage.flush();
+        }
+        self.open_data_dir(data_dir);
+    }
+
+    // 手で読み込み直す（操作の記録に残す）
+    fn reload(&mut self) {
+        if let Err(e) = self.load_from_file() {
+            eprintln!("Failed to load file: {}", e);
+        }
+        self.audit(audit::Action::Load {
+            path: self.data_dir.display().to_string(),
+        });
+    }
+
+    // 注文の中身（「プレーン×3 チョコ×1」）
+    fn lines_summary(&self, lines: &[OrderLine]) -> String {
+        lines
+            .iter()
+            .map(|line| {
+                let name = self
+                    .menu
+                    .get(line.food.id)
+                    .map_or(line.food.name.as_str(), |item| item.name.as_str());
+                format!("{}×{}", name, line.n)
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
 
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            // 保存
-            let path = "sold_food.json";
-            if let Err(e) = self.load_sold_food_from_file(std::path::Path::new(path)) {
-                eprintln!("Failed to load file: {}", e);
+    // 表示しているセッションの注文を新しい順に並べる（取り消した注文、無効にした注文も出す）
+    // 古い注文は `RECENT_ORDERS` 件ずつめくって見る
+    // 修正・無効にするボタンが押されたら、その注文の `history` 上の位置を返す
+    fn recent_orders_ui(&mut self, ui: &mut egui::Ui) -> Option<RecentAction> {
+        let shown = self.shown_session_id();
+        // 注文と `history` 上の位置（取り消した注文は None）
+        let mut orders: Vec<(&FoodOrder, Option<usize>)> = self
+            .history
+            .iter()
+            .enumerate()
+            .filter_map(|(i, order)| match order {
+                Order::Food(order) => Some((order, Some(i))),
+                _ => None,
+            })
+            .chain(self.undone_orders.iter().map(|order| (order, None)))
+            .filter(|(order, _)| order.session == shown)
+            .collect();
+        orders.sort_by_key(|(order, _)| std::cmp::Reverse(order.time));
+
+        let pages = orders.len().div_ceil(RECENT_ORDERS).max(1);
+        let mut page = self.recent_page.min(pages - 1);
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(page > 0, egui::Button::new("← 新しい注文"))
+                .clicked()
+            {
+                page -= 1;
             }
-            // 注文個数の履歴を保存
-            let path = "history.json";
-            if let Err(e) = self.load_history_from_file(std::path::Path::new(path)) {
-                eprintln!("Failed to load file: {}", e);
+            ui.label(format!("{}/{}", page + 1, pages));
+            if ui
+                .add_enabled(page + 1 < pages, egui::Button::new("古い注文 →"))
+                .clicked()
+            {
+                page += 1;
             }
-            Ok(())
-        }
-    }
+        });
 
-    pub fn load_sold_food_from_file(
-        &mut self,
-        path: &std::path::Path,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        if !std::fs::exists(path)? {
-            return Ok(());
-        }
+        let mut action = None;
+        egui::Grid::new("recent_orders_grid")
+            .num_columns(4)
+            .striped(true)
+            .show(ui, |ui| {
+                for (order, index) in orders
+                    .into_iter()
+                    .skip(page * RECENT_ORDERS)
+                    .take(RECENT_ORDERS)
+                {
+                    let time = order.time.with_timezone(&chrono::Local).format("%H:%M:%S");
+                    let mut summary = self.lines_summary(&order.lines);
+                    if !order.amendments.is_empty() {
+                        summary += "（修正済み）";
+                    }
+                    let texts = [time.to_string(), summary, format!("{}玉", order.count())];
+                    let counted = index.is_some() && !order.is_void();
+                    for text in texts {
+                        let mut text = egui::RichText::new(text);
+                        if !counted {
+                            text = text.strikethrough().weak();
+                        }
+                        ui.label(text);
+                    }
 
-        let file = std::fs::File::open(path)?;
-        self.sold_food = serde_json::from_reader(file)?;
+                    match (index, &order.void) {
+                        (None, _) => {
+                            ui.weak("取り消し");
+                        }
+                        (Some(_), Some(v)) => {
+                            ui.weak(format!("無効：{}", v.reason));
+                        }
+                        (Some(i), None) => {
+                            ui.horizontal(|ui| {
+                                if ui.small_button("修正").clicked() {
+                                    action = Some(RecentAction::Amend(i));
+                                }
+                                if ui.small_button("無効にする").clicked() {
+                                    action = Some(RecentAction::Void(i));
+                                }
+                            });
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+        self.recent_page = page;
 
-        Ok(())
+        action
+    }
+
+    // 注文の味や個数を直す（修正前の行も記録に残す）
+    pub fn amend_order(&mut self, index: usize, lines: Vec<OrderLine>) {
+        let Some(Order::Food(order)) = self.history.get(index) else {
+            return;
+        };
+        let action = audit::Action::Amend {
+            order: order.time,
+            before: self.lines_summary(&order.lines),
+            after: self.lines_summary(&lines),
+        };
+        let order_ref = OrderRef::Time(order.time);
+        let amendment = Amendment {
+            time: chrono::Utc::now(),
+            before: order.lines.clone(),
+            after: lines,
+        };
+        self.record(Event::Amend {
+            order: order_ref,
+            amendment,
+        });
+        self.audit(action);
+    }
+
+    // 注文を無効にする（記録は残し、個数や売上には数えない）
+    pub fn void_order(&mut self, index: usize, reason: &str) {
+        let Some(Order::Food(order)) = self.history.get(index) else {
+            return;
+        };
+        let action = audit::Action::Void {
+            order: order.time,
+            summary: self.lines_summary(&order.lines),
+            reason: reason.trim().to_owned(),
+        };
+        let order_ref = OrderRef::Time(order.time);
+        self.audit(action);
+        self.record(Event::Void {
+            order: order_ref,
+            time: chrono::Utc::now(),
+            reason: reason.trim().to_owned(),
+        });
     }
 
     pub fn load_history_from_file(
@@ -205,28 +1287,20 @@ impl TemplateApp {
             return Ok(());
         }
 
-        let file = std::fs::File::open(path)?;
-        self.history = serde_json::from_reader(file)?;
+        self.history = crate::schema::from_slice(&std::fs::read(path)?)?;
 
         Ok(())
     }
-
-    pub fn get_last_history(&self) -> Option<usize> {
-        let n = self
-            .history
-            .iter()
-            .enumerate()
-            .filter_map(|(i, h)| if let Order::Reset = h { Some(i) } else { None })
-            .last();
-
-        n
-    }
 }
 
 impl eframe::App for TemplateApp {
     /// Called by the frame work to save state before shutdown.
-    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+    fn save(&mut self, _storage: &mut dyn eframe::Storage) {
         // eframe::set_value(storage, eframe::APP_KEY, self);
+
+        // ネイティブ版はデータフォルダのファイルに保存している
+        #[cfg(target_arch = "wasm32")]
+        self.save_to_storage(_storage);
     }
 
     /// Called each time the UI needs repainting, which may be many times per second.
@@ -234,14 +1308,22 @@ impl eframe::App for TemplateApp {
         // Put your widgets into a `SidePanel`, `TopBottomPanel`, `CentralPanel`, `Window` or `Area`.
         // For inspiration and more examples, go to https://emilk.github.io/egui
 
+        // web 版は注文ごとにブラウザへ保存する（ページを閉じても消えないように）
+        if std::mem::take(&mut self.unsaved) {
+            #[cfg(target_arch = "wasm32")]
+            if let Some(storage) = _frame.storage_mut() {
+                self.save_to_storage(storage);
+            }
+        }
+
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             // The top panel is often a good place for a menu bar:
 
             egui::menu::bar(ui, |ui| {
                 // NOTE: no File->Quit on web pages!
                 let is_web = cfg!(target_arch = "wasm32");
-                if !is_web {
-                    ui.menu_button("File", |ui| {
+                ui.menu_button("File", |ui| {
+                    if !is_web {
                         if ui.button("Quit").clicked() {
                             ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                         }
@@ -253,27 +1335,425 @@ impl eframe::App for TemplateApp {
                         }
                         // load
                         if ui.button("Load").clicked() {
-                            if let Err(e) = self.load_from_file() {
-                                eprintln!("Failed to load file: {}", e);
+                            self.reload();
+                        }
+                        // 別のデータフォルダを開く
+                        #[cfg(not(target_arch = "wasm32"))]
+                        if ui.button("Open data folder…").clicked() {
+                            ui.close_menu();
+                            self.pick_data_dir(_frame);
+                        }
+                        ui.separator();
+                    }
+                    // 記録の書き出し（web 版はダウンロード）
+                    for (text, csv) in [("Export JSON…", false), ("Export CSV…", true)] {
+                        if ui.button(text).clicked() {
+                            ui.close_menu();
+                            if let Err(e) = self.export_ledger(csv) {
+                                eprintln!("Failed to save file: {}", e);
                             }
                         }
-                        // reset
-                        if ui.button("Reset").clicked() {
-                            self.sold_food.clear();
-                            self.save_to_file()
-                                .unwrap_or_else(|e| eprintln!("Failed to save file: {}", e));
-
-                            self.history.push(Order::Reset);
+                    }
+                    // 味ごとの個数の書き出し
+                    if ui.button("Export summary CSV…").clicked() {
+                        ui.close_menu();
+                        if let Err(e) = self.export_summary() {
+                            eprintln!("Failed to save file: {}", e);
                         }
-                    });
-                    ui.add_space(16.0);
+                    }
+                    // 印刷用の報告書
+                    if ui.button("Export session report…").clicked() {
+                        ui.close_menu();
+                        if let Err(e) = self.export_report() {
+                            eprintln!("Failed to save file: {}", e);
+                        }
+                    }
+                    // 操作の記録の書き出し
+                    if ui.button("Export audit log…").clicked() {
+                        ui.close_menu();
+                        let name =
+                            format!("audit-{}.csv", chrono::Local::now().format("%Y%m%d-%H%M%S"));
+                        if let Err(e) = ledger::save_csv(&name, &audit::to_csv(&self.audit)) {
+                            eprintln!("Failed to save file: {}", e);
+                        }
+                    }
+                    // 書き出した記録を読み込んで足す
+                    if ui.button("Import…").clicked() {
+                        ui.close_menu();
+                        ledger::pick_json(&self.import_inbox, ctx);
+                    }
+                });
+                ui.add_space(16.0);
+
+                if ui.button("メニュー編集").clicked() && self.menu_draft.is_none() {
+                    self.menu_draft = Some(self.menu.clone());
+                }
+                if ui.button("レジ締め").clicked() && self.cash_up.is_none() {
+                    self.cash_up = Some(CashUpForm::new(
+                        self.current_session().map_or(0, |session| session.float),
+                    ));
+                }
+                if ui.button("新しいセッション").clicked() && self.new_session.is_none() {
+                    self.new_session = Some(NewSessionForm::default());
+                }
+                ui.toggle_value(&mut self.show_recent, "最近の注文");
+                ui.toggle_value(&mut self.show_audit, "操作の記録");
+                ui.toggle_value(&mut self.show_staff_report, "担当ごとの売上");
+                if ui.button("セッションの比較").clicked() && self.compare.is_none() {
+                    let summaries = compare::summaries(&self.history, Some(&self.menu), "この端末");
+                    self.compare = Some(Dashboard::new(summaries));
                 }
+                ui.add_space(16.0);
+
+                let staff = if self.staff.is_empty() {
+                    "担当：なし".to_owned()
+                } else {
+                    format!("担当：{}", self.staff)
+                };
+                if ui.button(staff).clicked() && self.login_form.is_none() {
+                    self.login_form = Some(LoginForm::new(&self.staff));
+                }
+                ui.add_space(16.0);
 
                 egui::widgets::global_theme_preference_buttons(ui);
+
+                if !is_web {
+                    ui.add_space(16.0);
+                    ui.weak(format!("データ: {}", self.data_dir.display()));
+                }
             });
         });
 
+        if self.show_audit {
+            egui::Window::new("操作の記録")
+                .open(&mut self.show_audit)
+                .show(ctx, |ui| {
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        egui::Grid::new("audit_grid")
+                            .num_columns(4)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                // 新しい順
+                                for entry in self.audit.iter().rev() {
+                                    let time = entry.time.with_timezone(&chrono::Local);
+                                    ui.label(time.format("%m-%d %H:%M:%S").to_string());
+                                    ui.label(&entry.staff);
+                                    ui.label(entry.action.name());
+                                    ui.label(entry.action.detail());
+                                    ui.end_row();
+                                }
+                            });
+                    });
+                });
+        }
+
+        if let Some(mut form) = self.login_form.take() {
+            let mut open = true;
+            let mut action = LoginAction::None;
+
+            egui::Window::new("担当の交代")
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    action = form.ui(ui, &mut self.settings);
+                });
+
+            match action {
+                LoginAction::Login(name) => self.log_in(&name),
+                LoginAction::Logout => self.log_in(""),
+                LoginAction::Edit(what) => {
+                    self.save_settings();
+                    self.audit(audit::Action::Staff { what });
+                    if open {
+                        self.login_form = Some(form);
+                    }
+                }
+                LoginAction::None => {
+                    if open {
+                        self.login_form = Some(form);
+                    }
+                }
+            }
+        }
+
+        if self.show_staff_report {
+            let session = self.shown_session_id();
+            let totals = staff::totals(self.session_orders(session));
+            let shifts = staff::shifts(self.session_orders(session));
+
+            egui::Window::new("担当ごとの売上")
+                .open(&mut self.show_staff_report)
+                .show(ctx, |ui| {
+                    staff::report_ui(ui, &totals, &shifts);
+                });
+        }
+
+        if let Some(mut dashboard) = self.compare.take() {
+            let mut open = true;
+            let mut pick = false;
+
+            // 選ばれた記録ファイルのセッションを足す
+            let archive = self
+                .archive_inbox
+                .lock()
+                .ok()
+                .and_then(|mut slot| slot.take());
+            if let Some(bytes) = archive {
+                if let Err(e) = dashboard.add_archive(&bytes) {
+                    eprintln!("Failed to load file: {}", e);
+                }
+            }
+
+            egui::Window::new("セッションの比較")
+                .open(&mut open)
+                .default_width(600.0)
+                .show(ctx, |ui| {
+                    pick = dashboard.ui(ui);
+                });
+
+            if pick {
+                ledger::pick_json(&self.archive_inbox, ctx);
+            }
+            if open {
+                self.compare = Some(dashboard);
+            }
+        }
+
+        if let Some(mut draft) = self.menu_draft.take() {
+            let mut open = true;
+            let mut apply = false;
+            let mut undo = false;
+
+            egui::Window::new("メニュー編集")
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    draft.editor_ui(ui, &mut self.menu_new_name);
+
+                    ui.separator();
+
+                    ui.horizontal(|ui| {
+                        apply = ui.button("適用").clicked();
+                        undo = ui
+                            .add_enabled(
+                                matches!(self.commands.last_undo(), Some(Command::Menu { .. })),
+                                egui::Button::new("元に戻す"),
+                            )
+                            .clicked();
+                    });
+                });
+
+            if apply {
+                self.apply_menu(draft.clone());
+            }
+            if undo {
+                self.undo();
+                draft = self.menu.clone();
+            }
+            if open {
+                self.menu_draft = Some(draft);
+            }
+        }
+
+        if let Some(error) = &self.storage_error {
+            let mut open = true;
+
+            egui::Window::new("保存できませんでした")
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    ui.label("ブラウザに記録を保存できませんでした");
+                    ui.label(error);
+                });
+
+            if !open {
+                self.storage_error = None;
+            }
+        }
+
+        if !self.load_problems.is_empty() {
+            let mut open = true;
+
+            egui::Window::new("記録の確認")
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    ui.label("読み込んだ記録に食い違いがありました");
+
+                    ui.separator();
+
+                    for problem in &self.load_problems {
+                        ui.label(problem);
+                    }
+                });
+
+            if !open {
+                self.load_problems.clear();
+            }
+        }
+
+        // 選ばれた記録ファイルを読み込む
+        let imported = self
+            .import_inbox
+            .lock()
+            .ok()
+            .and_then(|mut slot| slot.take());
+        if let Some(bytes) = imported {
+            if let Err(e) = self.import_ledger(&bytes) {
+                eprintln!("Failed to load file: {}", e);
+            }
+        }
+
+        if let Some(mut form) = self.cash_up.take() {
+            let mut open = true;
+            let mut close = false;
+            let cash_sales = self.session_cash_sales();
+
+            egui::Window::new("レジ締め")
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    close = form.ui(ui, cash_sales);
+                });
+
+            if close {
+                self.close_session(&form);
+            } else if open {
+                self.cash_up = Some(form);
+            }
+        }
+
+        if let Some(mut form) = self.amend_form.take() {
+            let mut open = true;
+            let mut amend = false;
+
+            egui::Window::new("注文の修正")
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    amend = form.ui(ui, &self.menu);
+                });
+
+            if amend {
+                self.amend_order(form.order, form.lines);
+            } else if open {
+                self.amend_form = Some(form);
+            }
+        }
+
+        if let Some(mut form) = self.void_form.take() {
+            let mut open = true;
+            let mut void = false;
+
+            egui::Window::new("注文の無効化")
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    void = form.ui(ui);
+                });
+
+            if void {
+                self.void_order(form.order, &form.reason);
+            } else if open {
+                self.void_form = Some(form);
+            }
+        }
+
+        if let Some(mut form) = self.new_session.take() {
+            let mut open = true;
+            let mut start = false;
+
+            egui::Window::new("新しいセッション")
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    form.ui(ui);
+
+                    ui.separator();
+
+                    start = ui.button("始める").clicked();
+                });
+
+            if start {
+                self.start_session(&form);
+            } else if open {
+                self.new_session = Some(form);
+            }
+        }
+
+        if let Some(mut register) = self.register.take() {
+            let text_size = ctx.screen_rect().width() * 0.03;
+            let mut open = true;
+            let mut action = RegisterAction::None;
+
+            egui::Window::new("会計")
+                .open(&mut open)
+                .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    action = register.ui(ui, text_size);
+                });
+
+            match action {
+                RegisterAction::Pay { tendered, change } => {
+                    self.record_payment(register.order, tendered, change);
+                }
+                RegisterAction::Skip => {}
+                RegisterAction::None => {
+                    if open {
+                        self.register = Some(register);
+                    }
+                }
+            }
+        }
+
+        // 会計中は、次の注文も取り消しもさせない（会計している注文が変わらないように）
+        let paying = self.register.is_some();
+
+        // 文字を入力しているときは、入力欄の取り消しに任せる
+        if !ctx.wants_keyboard_input() && !paying {
+            let (redo, undo) = ctx.input_mut(|input| {
+                (
+                    input.consume_shortcut(&REDO_SHORTCUT),
+                    input.consume_shortcut(&UNDO_SHORTCUT),
+                )
+            });
+            if redo {
+                self.redo();
+            } else if undo {
+                self.undo();
+            }
+        }
+
+        if self.show_recent {
+            egui::SidePanel::right("recent_orders").show(ctx, |ui| {
+                if paying {
+                    ui.disable();
+                }
+                ui.heading("最近の注文");
+                if let Some(session) = self.shown_session() {
+                    ui.label(&session.name);
+                }
+                ui.separator();
+                egui::ScrollArea::vertical().show(ui, |ui| match self.recent_orders_ui(ui) {
+                    Some(RecentAction::Amend(index)) => {
+                        if let Some(Order::Food(order)) = self.history.get(index) {
+                            self.amend_form = Some(AmendForm::new(index, order.lines.clone()));
+                        }
+                    }
+                    Some(RecentAction::Void(index)) => {
+                        if let Some(Order::Food(order)) = self.history.get(index) {
+                            let summary = format!(
+                                "{}　{}　¥{}",
+                                order.time.with_timezone(&chrono::Local).format("%H:%M:%S"),
+                                self.lines_summary(&order.lines),
+                                order.total(),
+                            );
+                            self.void_form = Some(VoidForm::new(index, summary));
+                        }
+                    }
+                    None => {}
+                });
+            });
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
+            if paying {
+                ui.disable();
+            }
             let range = ui.clip_rect().width();
             let text_size = range * 0.03;
 
@@ -305,20 +1785,54 @@ impl eframe::App for TemplateApp {
             // 大きく真ん中の上に配置
             // ui.heading("売れた玉数");
             ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
-                let n = self.history.len() as isize
-                    - self.get_last_history().map(|x| x as isize).unwrap_or(-1)
-                    - 1;
+                // 表示するセッション
+                let current = self.current_session_id();
+                let shown = self.shown_session_id();
+                let shown_name = self
+                    .shown_session()
+                    .map_or(String::new(), |session| session.name.clone());
+                egui::ComboBox::from_label("セッション")
+                    .selected_text(shown_name)
+                    .show_ui(ui, |ui| {
+                        for range in session::sessions(&self.history) {
+                            let id = range.session.id;
+                            let text = if id == current {
+                                format!("{}（現在）", range.session.name)
+                            } else {
+                                range.session.name.clone()
+                            };
+                            if ui.selectable_label(shown == id, text).clicked() {
+                                self.selected_session = (id != current).then_some(id);
+                            }
+                        }
+                    });
+
+                let sold: usize = self.session_orders(shown).map(|order| order.count()).sum();
+                let n = self.session_orders(shown).count();
 
                 // ui.horizontal(|ui| {
                 ui.label(
                     egui::RichText::new(format!(
-                        "{}玉売れました！（注文数：{}）",
-                        self.sold_food.len(),
+                        "{}玉売れました！（注文数：{}）　売上：¥{}",
+                        sold,
                         n,
+                        self.session_revenue(shown),
                     ))
                     .size(text_size * 2.0),
                 );
                 // });
+
+                // 支払い方法ごとの売上
+                let by_method = self
+                    .session_revenue_by_method(shown)
+                    .into_iter()
+                    .map(|(name, total)| format!("{} ¥{}", name, total))
+                    .collect::<Vec<_>>()
+                    .join("　");
+                ui.label(egui::RichText::new(by_method).size(text_size));
+
+                // グラフの切り替え
+                self.chart.ui(ui);
             });
 
             // 下の方
@@ -335,7 +1849,7 @@ impl eframe::App for TemplateApp {
                         .button(egui::RichText::new("+1").size(text_size))
                         .clicked()
                     {
-                        self.n += 1;
+                        self.set_quantity(self.n + 1);
                     }
 
                     ui.label(egui::RichText::new(format!("{}", self.n)).size(text_size));
@@ -344,12 +1858,11 @@ impl eframe::App for TemplateApp {
                         .button(egui::RichText::new("-1").size(text_size))
                         .clicked()
                     {
-                        self.n -= 1;
+                        self.set_quantity(self.n.saturating_sub(1));
                     }
 
                     // 0.8倍の範囲にボタンを配置
-                    // プレーン、チョコ、いちご、はちみつ、シナモン
-                    let food_list = &["プレーン", "チョコ", "いちご", "はちみつ", "シナモン"];
+                    let food_list: Vec<_> = self.menu.active_items().cloned().collect();
                     let spacing = range * 0.8 / (food_list.len() as f32 + 1.0); // ボタンの間にスペースを加える
 
                     ui.add_space(range * 0.05); // 左スペースを追加してボタンを中央寄せに
@@ -358,130 +1871,172 @@ impl eframe::App for TemplateApp {
 
                         ui.add_space(space); // 左スペースを追加してボタンを中央寄せに
                         if ui
-                            .button(egui::RichText::new(*food).size(text_size))
+                            .add(
+                                egui::Button::new(
+                                    egui::RichText::new(&food.name)
+                                        .size(text_size)
+                                        .color(egui::Color32::BLACK),
+                                )
+                                .fill(food.color32()),
+                            )
                             .clicked()
                         {
-                            self.add_sold_food(food.to_string());
+                            self.add_to_cart(food.id);
                         }
                     }
 
-                    // 取り消し
+                    // 取り消し・やり直し
                     ui.add_space(spacing * 0.2); // 左スペースを追加してボタンを中央寄せに
                     if ui
                         .button(egui::RichText::new("取り消し").size(text_size))
+                        .on_hover_text("Ctrl+Z")
                         .clicked()
                     {
-                        if let Some(history_n) = self.history.pop() {
-                            match history_n {
-                                Order::Food(_, n) => {
-                                    for _ in 0..n {
-                                        self.sold_food.pop();
-                                    }
-                                }
-                                Order::Reset => {
-                                    // 前のresetまでの履歴を取り出して、それを履歴に追加
-
-                                    if let Some(n) = self.get_last_history() {
-                                        // println!("reset1");
-
-                                        self.sold_food = self.history[n + 1..]
-                                            .iter()
-                                            .flat_map(|h| {
-                                                if let Order::Food(f, n) = h {
-                                                    vec![f.clone(); *n]
-                                                } else {
-                                                    unreachable!()
-                                                }
-                                            })
-                                            .collect();
-                                    } else {
-                                        // println!("reset2");
-
-                                        self.sold_food = self
-                                            .history
-                                            .iter()
-                                            .flat_map(|h| {
-                                                if let Order::Food(f, n) = h {
-                                                    vec![f.clone(); *n]
-                                                } else {
-                                                    unreachable!()
-                                                }
-                                            })
-                                            .collect();
-                                    }
-                                }
-                            }
+                        self.undo();
+                    }
+                    if ui
+                        .add_enabled(
+                            self.commands.can_redo(),
+                            egui::Button::new(egui::RichText::new("やり直し").size(text_size)),
+                        )
+                        .on_hover_text("Ctrl+Y")
+                        .clicked()
+                    {
+                        self.redo();
+                    }
+                });
+
+                // カート
+                ui.horizontal(|ui| {
+                    let mut remove = None;
+                    for (i, line) in self.cart.iter().enumerate() {
+                        let name = self
+                            .menu
+                            .get(line.food.id)
+                            .map_or(line.food.name.as_str(), |item| item.name.as_str());
+                        ui.label(
+                            egui::RichText::new(format!("{}×{} ¥{}", name, line.n, line.price))
+                                .size(text_size),
+                        );
+                        if ui
+                            .button(egui::RichText::new("×").size(text_size))
+                            .clicked()
+                        {
+                            remove = Some(i);
                         }
+                        ui.add_space(text_size);
+                    }
+                    if let Some(i) = remove {
+                        self.cart.remove(i);
+                    }
 
-                        self.save_to_file()
-                            .unwrap_or_else(|e| eprintln!("Failed to save file: {}", e));
+                    let total: u32 = self.cart.iter().map(|line| line.price).sum();
+                    ui.label(egui::RichText::new(format!("合計 ¥{}", total)).size(text_size));
+
+                    if ui
+                        .add_enabled(
+                            !self.cart.is_empty(),
+                            egui::Button::new(egui::RichText::new("注文確定").size(text_size)),
+                        )
+                        .clicked()
+                    {
+                        let lines = std::mem::take(&mut self.cart);
+                        self.add_sold_food(lines);
+
+                        // 現金のときだけお釣りを計算する
+                        let cash = self
+                            .settings
+                            .payment_method(&self.payment_method)
+                            .is_some_and(|method| method.cash);
+                        if let (true, Some(Order::Food(order))) = (cash, self.history.last()) {
+                            self.register =
+                                Some(Register::new(self.history.len() - 1, order.total()));
+                        }
+                    }
+                    if ui
+                        .add_enabled(
+                            !self.cart.is_empty(),
+                            egui::Button::new(egui::RichText::new("クリア").size(text_size)),
+                        )
+                        .clicked()
+                    {
+                        self.cart.clear();
                     }
                 });
-            });
-
-            let binding = self.sold_food_count();
-            let max = binding
-                .iter()
-                .cloned()
-                .map(|(_, count)| count)
-                .max()
-                .unwrap_or(0);
 
-            {
-                use egui_plotter::EguiBackend;
-                use plotters::prelude::*;
-
-                ui.scope_builder(
-                    egui::UiBuilder::new().max_rect({
-                        // 中心に向かって0.6倍
-                        let rect = ui.clip_rect();
-
-                        // println!("rect: {:?}", rect);
-
-                        let center = rect.center();
-                        let width = (rect.width() * 0.6).max(200.0);
-                        let height = (rect.height() * 0.6).max(200.0);
-                        let correct = egui::vec2(width, height);
-                        let correct = egui::Rect::from_center_size(center, correct);
-                        correct
-                    }),
-                    |ui| {
-                        let root = EguiBackend::new(ui).into_drawing_area();
-                        let mut chart = ChartBuilder::on(&root)
-                            .margin(5)
-                            .x_label_area_size(0)
-                            .y_label_area_size(30)
-                            .build_cartesian_2d((0u32..4u32).into_segmented(), 0..max + 1)
-                            .unwrap();
-
-                        chart.configure_mesh().draw().unwrap();
-
-                        let sold_food_count = self.sold_food_count();
-
-                        chart
-                            .draw_series(
-                                Histogram::vertical(&chart)
-                                    .style(RED.mix(0.5).filled())
-                                    .data(
-                                        sold_food_count
-                                            .iter()
-                                            .enumerate()
-                                            .map(|(i, (_, count))| (i as u32, *count)),
-                                    ),
+                // 支払い方法
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new("支払い：").size(text_size));
+                    for method in &self.settings.payment_methods {
+                        if ui
+                            .selectable_label(
+                                self.payment_method == method.name,
+                                egui::RichText::new(&method.name).size(text_size),
                             )
-                            .unwrap()
-                            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], &RED));
+                            .clicked()
+                        {
+                            self.payment_method = method.name.clone();
+                        }
+                    }
+                });
+            });
 
-                        root.present().unwrap();
-                    },
-                );
-            }
+            ui.scope_builder(
+                egui::UiBuilder::new().max_rect({
+                    // 中心に向かって0.6倍
+                    let rect = ui.clip_rect();
+
+                    // println!("rect: {:?}", rect);
+
+                    let center = rect.center();
+                    let width = (rect.width() * 0.6).max(200.0);
+                    let height = (rect.height() * 0.6).max(200.0);
+                    let correct = egui::vec2(width, height);
+                    let correct = egui::Rect::from_center_size(center, correct);
+                    correct
+                }),
+                |ui| match self.chart.view {
+                    chart::View::Flavors => {
+                        let flavors = chart::flavors(self.sold_food_count(), &self.menu);
+                        chart::draw_flavors(ui, &flavors, self.chart.percent);
+                    }
+                    chart::View::OverTime => {
+                        let data = chart::TimeSeries::new(
+                            self.session_orders(self.shown_session_id()),
+                            &self.menu,
+                            self.chart.bucket,
+                            chart::MAX_BUCKETS,
+                        );
+                        if data.minutes != self.chart.bucket {
+                            ui.label(format!(
+                                "区切りが多すぎるので{}分ごとにしています",
+                                data.minutes
+                            ));
+                        }
+                        chart::draw_over_time(ui, &data, self.chart.style);
+                    }
+                },
+            );
 
             ui.separator();
         });
     }
 }
 
+// 最近の注文の一覧で押されたボタン
+enum RecentAction {
+    Amend(usize),
+    Void(usize),
+}
+
+// 最近の注文の一覧に出す件数
+const RECENT_ORDERS: usize = 30;
+
+const UNDO_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Z);
+const REDO_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Y);
+
 fn powered_by_egui_and_eframe(ui: &mut egui::Ui) {
     ui.horizontal(|ui| {
         ui.spacing_mut().item_spacing.x = 0.0;
@@ -495,3 +2050,82 @@ fn powered_by_egui_and_eframe(ui: &mut egui::Ui) {
         ui.label(".");
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{plain, temp_dir};
+
+    // プレーン3玉と2玉を受けたところ
+    fn app_with_orders(name: &str) -> TemplateApp {
+        let mut app = TemplateApp {
+            data_dir: temp_dir(name),
+            ..TemplateApp::default()
+        };
+        app.push(Order::Food(plain("2024-11-02T01:00:00Z", 3)));
+        app.push(Order::Food(plain("2024-11-02T01:05:00Z", 2)));
+        app
+    }
+
+    fn plain_count(app: &TemplateApp) -> usize {
+        app.sold_food_count()
+            .into_iter()
+            .find(|(id, _, _)| *id == 1)
+            .map_or(0, |(_, _, n)| n)
+    }
+
+    // ログを最初から読み直した `history`
+    fn replayed(app: &TemplateApp) -> Vec<Order> {
+        event_log::replay(&app.data_path("events.jsonl"))
+            .unwrap()
+            .history
+    }
+
+    #[test]
+    fn counts_cash_and_unrecorded_payments_as_cash_sales() {
+        let mut app = app_with_orders("cash_sales");
+        for (order, method) in app.history.iter_mut().zip(["現金", "QR決済"]) {
+            if let Order::Food(order) = order {
+                order.payment_method = Some(method.to_owned());
+            }
+        }
+        // 支払い方法を記録する前の注文
+        app.push(Order::Food(plain("2024-11-02T01:10:00Z", 1)));
+
+        // 現金の300円と、支払い方法の無い古い注文の100円
+        assert_eq!(app.session_cash_sales(), 400);
+    }
+
+    #[test]
+    fn void_removes_order_from_counts_and_log() {
+        let mut app = app_with_orders("void_order");
+
+        app.void_order(0, "打ち間違い");
+
+        assert_eq!(plain_count(&app), 2);
+        assert_eq!(app.session_revenue(app.current_session_id()), 200);
+        let history = replayed(&app);
+        assert_eq!(history.len(), 2);
+        assert!(matches!(&history[0], Order::Food(order)
+            if order.void.as_ref().is_some_and(|void| void.reason == "打ち間違い")));
+        assert!(matches!(&history[1], Order::Food(order) if !order.is_void()));
+    }
+
+    #[test]
+    fn amend_changes_counts_and_log() {
+        let mut app = app_with_orders("amend_order");
+
+        app.amend_order(1, plain("2024-11-02T01:05:00Z", 5).lines);
+
+        assert_eq!(plain_count(&app), 8);
+        assert_eq!(app.session_revenue(app.current_session_id()), 800);
+        let history = replayed(&app);
+        let Some(Order::Food(order)) = history.get(1) else {
+            panic!("amended order is missing")
+        };
+        assert_eq!(order.count(), 5);
+        assert_eq!(order.amendments.len(), 1);
+        // 会計したときの金額は修正する前のまま
+        assert_eq!(order.charged_total(), 200);
+    }
+}
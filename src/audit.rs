@@ -0,0 +1,181 @@
+//! 取り消しや無効化など、記録を変える操作の記録（誰が・いつ・何をしたか）
+//! `audit.jsonl` に1行ずつ追記する
+
+/// 記録に残す操作
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub enum Action {
+    // 新しいセッションを始めた（以前のリセット）
+    NewSession {
+        name: String,
+    },
+    // レジ締めをした
+    CashUp {
+        session: String,
+        difference: i64,
+    },
+    // 操作を取り消した・やり直した
+    Undo {
+        what: String,
+    },
+    Redo {
+        what: String,
+    },
+    // 注文を無効にした
+    Void {
+        order: chrono::DateTime<chrono::Utc>,
+        summary: String,
+        reason: String,
+    },
+    // 注文を修正した
+    Amend {
+        order: chrono::DateTime<chrono::Utc>,
+        before: String,
+        after: String,
+    },
+    // メニューを変えた
+    Menu {
+        what: String,
+    },
+    // 担当を交代した（空なら担当なし）
+    Login {
+        name: String,
+    },
+    // 担当の一覧を変えた
+    Staff {
+        what: String,
+    },
+    // ファイルから読み込み直した
+    Load {
+        path: String,
+    },
+    // 書き出した記録を読み込んで足した
+    Import {
+        orders: usize,
+    },
+    // 読み込んだ記録の食い違いを知らせた
+    Problem {
+        what: String,
+    },
+}
+
+impl Action {
+    /// 一覧に出す操作の名前
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::NewSession { .. } => "新しいセッション",
+            Action::CashUp { .. } => "レジ締め",
+            Action::Undo { .. } => "取り消し",
+            Action::Redo { .. } => "やり直し",
+            Action::Void { .. } => "無効化",
+            Action::Amend { .. } => "修正",
+            Action::Menu { .. } => "メニュー変更",
+            Action::Login { .. } => "担当の交代",
+            Action::Staff { .. } => "担当の一覧の変更",
+            Action::Load { .. } => "読み込み",
+            Action::Import { .. } => "取り込み",
+            Action::Problem { .. } => "記録の食い違い",
+        }
+    }
+
+    /// 一覧に出す操作の中身
+    pub fn detail(&self) -> String {
+        let local = |time: &chrono::DateTime<chrono::Utc>| {
+            time.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        };
+
+        match self {
+            Action::NewSession { name } => name.clone(),
+            Action::CashUp {
+                session,
+                difference,
+            } => format!("{}（過不足 {:+}円）", session, difference),
+            Action::Undo { what } | Action::Redo { what } => what.clone(),
+            Action::Void {
+                order,
+                summary,
+                reason,
+            } => format!("{} {}：{}", local(order), summary, reason),
+            Action::Amend {
+                order,
+                before,
+                after,
+            } => format!("{} {} → {}", local(order), before, after),
+            Action::Menu { what } | Action::Staff { what } | Action::Problem { what } => {
+                what.clone()
+            }
+            Action::Login { name } if name.is_empty() => "担当なし".to_owned(),
+            Action::Login { name } => name.clone(),
+            Action::Load { path } => path.clone(),
+            Action::Import { orders } => format!("{}件の記録", orders),
+        }
+    }
+}
+
+/// 操作の記録1件
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct Entry {
+    pub time: chrono::DateTime<chrono::Utc>,
+    // 操作した人（担当の名前）
+    pub staff: String,
+    pub action: Action,
+}
+
+impl crate::schema::Versioned for Entry {
+    const MIGRATIONS: &'static [crate::schema::Migration] = &[crate::schema::unchanged];
+}
+
+impl crate::schema::Versioned for Vec<Entry> {
+    const MIGRATIONS: &'static [crate::schema::Migration] = &[crate::schema::unchanged];
+}
+
+/// `audit.jsonl` を読む
+/// 書き込み途中で落ちて壊れた行は読み飛ばす
+pub fn load(path: &std::path::Path) -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
+    if !std::fs::exists(path)? {
+        return Ok(vec![]);
+    }
+
+    let bytes = std::fs::read(path)?;
+    let (version, lines) = crate::event_log::read_lines::<Entry>(&bytes)?;
+    let mut entries = vec![];
+    for line in lines {
+        match line.value {
+            Ok(entry) => entries.extend(entry),
+            Err(e) => eprintln!(
+                "Skipped broken line of {}:{}: {}",
+                path.display(),
+                line.number,
+                e
+            ),
+        }
+    }
+
+    // 前の形式の記録は、今の形式で書き直す（このあとの追記と形式をそろえる）
+    if version < <Entry as crate::schema::Versioned>::version() {
+        crate::event_log::rewrite(path, &entries)?;
+    } else if !bytes.is_empty() && !bytes.ends_with(b"\n") {
+        crate::event_log::end_with_newline(path)?;
+    }
+
+    Ok(entries)
+}
+
+/// 操作の記録を CSV にする
+pub fn to_csv(entries: &[Entry]) -> String {
+    let mut csv = String::from("time,staff,action,detail\n");
+    for entry in entries {
+        let time = entry.time.with_timezone(&chrono::Local);
+        let row = [
+            time.format("%Y-%m-%d %H:%M:%S").to_string(),
+            crate::ledger::escape(&entry.staff),
+            entry.action.name().to_owned(),
+            crate::ledger::escape(&entry.action.detail()),
+        ];
+        csv += &row.join(",");
+        csv.push('\n');
+    }
+
+    csv
+}
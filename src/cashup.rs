@@ -0,0 +1,177 @@
+use crate::session::{NewSessionForm, Session};
+use crate::staff::{Shift, StaffTotal};
+
+/// 数える金種（円）
+pub const DENOMINATIONS: [u32; 10] = [10000, 5000, 2000, 1000, 500, 100, 50, 10, 5, 1];
+
+/// レジ締めの記録
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct CashUp {
+    pub time: chrono::DateTime<chrono::Utc>,
+    // 釣り銭準備金
+    pub float: u32,
+    // 金種と枚数
+    pub counted: Vec<(u32, u32)>,
+    // 準備金と現金売上から計算した、レジにあるはずの金額
+    pub expected: u32,
+}
+
+impl CashUp {
+    pub fn counted_total(&self) -> u32 {
+        self.counted.iter().map(|(yen, count)| yen * count).sum()
+    }
+
+    /// 過不足（プラスなら多い）
+    pub fn difference(&self) -> i64 {
+        self.counted_total() as i64 - self.expected as i64
+    }
+}
+
+/// 締めたセッションの集計（`reports/` に保存する）
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct SessionReport {
+    pub session: Session,
+    pub orders: usize,
+    pub sold: usize,
+    pub revenue: u32,
+    pub revenue_by_method: Vec<(String, u32)>,
+    pub sold_food_count: Vec<(String, usize)>,
+    pub cash_up: CashUp,
+    pub difference: i64,
+    // 担当ごと・シフトごとの集計（担当を記録する前の集計には無い）
+    #[serde(default)]
+    pub by_staff: Vec<StaffTotal>,
+    #[serde(default)]
+    pub shifts: Vec<Shift>,
+}
+
+impl crate::schema::Versioned for SessionReport {
+    const MIGRATIONS: &'static [crate::schema::Migration] = &[crate::schema::unchanged];
+}
+
+impl SessionReport {
+    pub fn save_to_dir(&self, dir: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(dir)?;
+
+        let time = self.cash_up.time.with_timezone(&chrono::Local);
+        let path = dir.join(format!("report-{}.json", time.format("%Y%m%d-%H%M%S")));
+        crate::file::write_atomic(&path, crate::schema::to_string_pretty(self)?.as_bytes())
+    }
+}
+
+/// レジ締めウィンドウの入力
+pub struct CashUpForm {
+    pub float: u32,
+    pub counts: [u32; DENOMINATIONS.len()],
+    // 締めたあとに始めるセッション
+    pub next: NewSessionForm,
+}
+
+impl CashUpForm {
+    pub fn new(float: u32) -> Self {
+        Self {
+            float,
+            counts: [0; DENOMINATIONS.len()],
+            next: NewSessionForm::default(),
+        }
+    }
+
+    pub fn cash_up(&self, expected_sales: u32) -> CashUp {
+        CashUp {
+            time: chrono::Utc::now(),
+            float: self.float,
+            counted: DENOMINATIONS
+                .iter()
+                .copied()
+                .zip(self.counts.iter().copied())
+                .collect(),
+            expected: self.float + expected_sales,
+        }
+    }
+
+    /// 締めるボタンが押されたら true
+    pub fn ui(&mut self, ui: &mut egui::Ui, cash_sales: u32) -> bool {
+        ui.horizontal(|ui| {
+            ui.label("釣り銭準備金");
+            ui.add(egui::DragValue::new(&mut self.float).prefix("¥"));
+        });
+
+        ui.separator();
+
+        egui::Grid::new("cash_up_grid")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                for (yen, count) in DENOMINATIONS.iter().zip(self.counts.iter_mut()) {
+                    ui.label(format!("¥{}", yen));
+                    ui.add(egui::DragValue::new(count).suffix("枚"));
+                    ui.label(format!("¥{}", yen * *count));
+                    ui.end_row();
+                }
+            });
+
+        ui.separator();
+
+        let cash_up = self.cash_up(cash_sales);
+        ui.label(format!("現金売上 ¥{}", cash_sales));
+        ui.label(format!("あるはずの金額 ¥{}", cash_up.expected));
+        ui.label(format!("数えた金額 ¥{}", cash_up.counted_total()));
+        let difference = cash_up.difference();
+        let color = if difference == 0 {
+            ui.visuals().text_color()
+        } else {
+            egui::Color32::RED
+        };
+        ui.colored_label(color, format!("過不足 {:+}円", difference));
+
+        ui.separator();
+
+        ui.label("次のセッション");
+        self.next.ui(ui);
+
+        ui.separator();
+
+        ui.button("締めて新しいセッションを始める").clicked()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1000円札1枚と100円玉4枚を数えたフォーム
+    fn counted_form(float: u32) -> CashUpForm {
+        let mut form = CashUpForm::new(float);
+        for (yen, count) in [(1000, 1), (100, 4)] {
+            let i = DENOMINATIONS.iter().position(|d| *d == yen).unwrap();
+            form.counts[i] = count;
+        }
+        form
+    }
+
+    #[test]
+    fn counts_each_denomination() {
+        let cash_up = counted_form(1000).cash_up(400);
+
+        assert_eq!(cash_up.counted.len(), DENOMINATIONS.len());
+        assert!(cash_up.counted.contains(&(1000, 1)));
+        assert!(cash_up.counted.contains(&(100, 4)));
+        assert!(cash_up.counted.contains(&(10000, 0)));
+        assert_eq!(cash_up.counted_total(), 1400);
+    }
+
+    #[test]
+    fn expects_float_plus_cash_sales() {
+        assert_eq!(counted_form(1000).cash_up(400).expected, 1400);
+        assert_eq!(counted_form(0).cash_up(0).expected, 0);
+    }
+
+    #[test]
+    fn difference_is_counted_minus_expected() {
+        assert_eq!(counted_form(1000).cash_up(400).difference(), 0);
+        // 足りない
+        assert_eq!(counted_form(1000).cash_up(500).difference(), -100);
+        // 多い
+        assert_eq!(counted_form(1000).cash_up(300).difference(), 100);
+    }
+}
@@ -0,0 +1,433 @@
+//! 中央パネルのグラフ
+
+use crate::menu::Menu;
+use crate::order::FoodOrder;
+
+/// どのグラフを出すか
+#[derive(Clone, Copy, PartialEq)]
+pub enum View {
+    // 味ごとの個数
+    Flavors,
+    // 時間ごとの個数と注文数
+    OverTime,
+}
+
+/// 時間ごとのグラフの描き方
+#[derive(Clone, Copy, PartialEq)]
+pub enum TimeStyle {
+    // 味ごとの玉数を積み上げた棒
+    Stacked,
+    // 味ごとの玉数の線
+    Lines,
+}
+
+/// 選べる区切りの長さ（分）
+pub const BUCKETS: [i64; 4] = [5, 15, 30, 60];
+
+/// 画面のグラフの区切りの数の上限
+/// 注文の時間がこれより多くの区切りにわたるなら、区切りを自動で長くする
+pub const MAX_BUCKETS: usize = 96;
+
+// `minutes` より長い区切り（選べる長さを超えたら倍にしていく）
+fn longer(minutes: i64) -> i64 {
+    BUCKETS
+        .into_iter()
+        .find(|bucket| *bucket > minutes)
+        .unwrap_or(minutes * 2)
+}
+
+/// グラフの選択
+pub struct ChartOptions {
+    pub view: View,
+    // 区切りの長さ（分）
+    pub bucket: i64,
+    pub style: TimeStyle,
+    // 味ごとのグラフを全体に対する割合で出す
+    pub percent: bool,
+}
+
+impl Default for ChartOptions {
+    fn default() -> Self {
+        Self {
+            view: View::Flavors,
+            bucket: 15,
+            style: TimeStyle::Stacked,
+            percent: false,
+        }
+    }
+}
+
+impl ChartOptions {
+    /// グラフを切り替えるボタン
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.view, View::Flavors, "味ごと");
+            ui.selectable_value(&mut self.view, View::OverTime, "時間ごと");
+
+            if self.view == View::Flavors {
+                ui.separator();
+                ui.checkbox(&mut self.percent, "割合");
+            }
+            if self.view == View::OverTime {
+                ui.separator();
+                for minutes in BUCKETS {
+                    ui.selectable_value(&mut self.bucket, minutes, format!("{}分", minutes));
+                }
+                ui.separator();
+                ui.selectable_value(&mut self.style, TimeStyle::Stacked, "積み上げ");
+                ui.selectable_value(&mut self.style, TimeStyle::Lines, "線");
+            }
+        });
+    }
+}
+
+/// 味ごとのグラフの棒1本
+pub struct Flavor {
+    pub name: String,
+    pub color: [u8; 3],
+    pub count: usize,
+}
+
+/// `sold_food_count` の個数に、メニューの番号で色を付ける
+/// メニューから外れた味は灰色にする
+pub fn flavors(counts: Vec<(u32, String, usize)>, menu: &Menu) -> Vec<Flavor> {
+    counts
+        .into_iter()
+        .map(|(id, name, count)| Flavor {
+            name,
+            color: menu.get(id).map_or([150, 150, 150], |item| item.color),
+            count,
+        })
+        .collect()
+}
+
+/// 味ごとの個数（`percent` なら全体に対する割合）を、名前と値を付けた棒で描く
+pub fn draw_flavors(ui: &mut egui::Ui, flavors: &[Flavor], percent: bool) {
+    use egui_plotter::EguiBackend;
+    use plotters::prelude::*;
+
+    let total: usize = flavors.iter().map(|flavor| flavor.count).sum();
+    let value = |flavor: &Flavor| {
+        if percent {
+            flavor.count as f64 * 100.0 / total.max(1) as f64
+        } else {
+            flavor.count as f64
+        }
+    };
+    let max = flavors.iter().map(value).fold(0.0, f64::max);
+    // 棒の上の数字が入るように少し高くする
+    let top = if percent { 100.0 } else { max * 1.15 + 1.0 };
+
+    let root = EguiBackend::new(ui).into_drawing_area();
+    let mut chart = ChartBuilder::on(&root)
+        .margin(5)
+        .x_label_area_size(30)
+        .y_label_area_size(40)
+        .build_cartesian_2d((0u32..flavors.len() as u32).into_segmented(), 0f64..top)
+        .unwrap();
+
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(flavors.len().max(1))
+        .x_label_formatter(&|x| match x {
+            SegmentValue::CenterOf(i) => flavors
+                .get(*i as usize)
+                .map_or(String::new(), |flavor| flavor.name.clone()),
+            _ => String::new(),
+        })
+        .y_label_formatter(&|y| {
+            if percent {
+                format!("{:.0}%", y)
+            } else {
+                format!("{}", *y as usize)
+            }
+        })
+        .draw()
+        .unwrap();
+
+    chart
+        .draw_series(
+            Histogram::vertical(&chart)
+                .margin(10)
+                .style_func(|x, _| match x {
+                    SegmentValue::Exact(i) | SegmentValue::CenterOf(i) => {
+                        let [r, g, b] = flavors
+                            .get(*i as usize)
+                            .map_or([150, 150, 150], |flavor| flavor.color);
+                        RGBColor(r, g, b).filled()
+                    }
+                    SegmentValue::Last => TRANSPARENT.filled(),
+                })
+                .data(
+                    flavors
+                        .iter()
+                        .enumerate()
+                        .map(|(i, flavor)| (i as u32, value(flavor))),
+                ),
+        )
+        .unwrap();
+
+    // 棒の上の数字
+    let style =
+        TextStyle::from(("sans-serif", 16).into_font()).pos(Pos::new(HPos::Center, VPos::Bottom));
+    chart
+        .draw_series(flavors.iter().enumerate().map(|(i, flavor)| {
+            let text = if percent {
+                format!("{:.1}%", value(flavor))
+            } else {
+                format!("{}", flavor.count)
+            };
+            Text::new(
+                text,
+                (SegmentValue::CenterOf(i as u32), value(flavor)),
+                style.clone(),
+            )
+        }))
+        .unwrap();
+
+    root.present().unwrap();
+}
+
+/// 1つの味の、区切りごとの玉数
+pub struct Series {
+    pub name: String,
+    pub color: [u8; 3],
+    pub counts: Vec<usize>,
+}
+
+/// 区切りごとの玉数と注文数
+pub struct TimeSeries {
+    // 最初の区切りの始まり（現地時刻）
+    pub start: chrono::NaiveDateTime,
+    pub minutes: i64,
+    // メニューの順。メニューから外れた名前の記録は末尾に追加する
+    pub series: Vec<Series>,
+    pub orders: Vec<usize>,
+}
+
+impl TimeSeries {
+    /// 注文を `minutes` 分ごとに数える
+    /// 区切りが `max_buckets` 個を超えるなら、収まるまで区切りを長くする（`minutes` に入る）
+    /// 名前を変えたメニューは番号で数える（`sold_food_count_in` と同じ）
+    pub fn new<'a>(
+        orders: impl Iterator<Item = &'a FoodOrder>,
+        menu: &Menu,
+        minutes: i64,
+        max_buckets: usize,
+    ) -> Self {
+        let orders: Vec<&FoodOrder> = orders.collect();
+        let local = |order: &FoodOrder| {
+            order
+                .time
+                .with_timezone(&chrono::Local)
+                .naive_local()
+                .and_utc()
+                .timestamp()
+        };
+
+        let first = orders.iter().map(|order| local(order)).min().unwrap_or(0);
+        let last = orders.iter().map(|order| local(order)).max().unwrap_or(0);
+
+        // 区切りの数は、最初と最後の注文の間の長さ ÷ 区切りの長さ + 2 を超えない
+        let mut minutes = minutes.max(1);
+        while (last - first) / (minutes * 60) + 2 > max_buckets.max(2) as i64 {
+            minutes = longer(minutes);
+        }
+        let seconds = minutes * 60;
+        let start = first - first.rem_euclid(seconds);
+        let len = if orders.is_empty() {
+            0
+        } else {
+            ((last - start) / seconds + 1) as usize
+        };
+
+        // 番号（0は古い記録）と味
+        let mut keys: Vec<(u32, Series)> = menu
+            .items
+            .iter()
+            .map(|item| {
+                let series = Series {
+                    name: item.name.clone(),
+                    color: item.color,
+                    counts: vec![0; len],
+                };
+                (item.id, series)
+            })
+            .collect();
+        let mut counts = vec![0; len];
+
+        for order in orders {
+            let i = ((local(order) - start) / seconds) as usize;
+            counts[i] += 1;
+
+            for line in &order.lines {
+                let found = keys.iter().position(|(id, series)| {
+                    if line.food.id != 0 {
+                        *id == line.food.id
+                    } else {
+                        series.name == line.food.name
+                    }
+                });
+                let series = match found {
+                    Some(k) => &mut keys[k].1,
+                    None => {
+                        keys.push((
+                            line.food.id,
+                            Series {
+                                name: line.food.name.clone(),
+                                color: [150, 150, 150],
+                                counts: vec![0; len],
+                            },
+                        ));
+                        &mut keys.last_mut().expect("just pushed").1
+                    }
+                };
+                series.counts[i] += line.n;
+            }
+        }
+
+        Self {
+            start: chrono::DateTime::from_timestamp(start, 0)
+                .unwrap_or_default()
+                .naive_utc(),
+            minutes,
+            // 一度も売れていない味は出さない
+            series: keys
+                .into_iter()
+                .map(|(_, series)| series)
+                .filter(|series| series.counts.iter().any(|n| *n > 0))
+                .collect(),
+            orders: counts,
+        }
+    }
+
+    /// `i` 番目の区切りの始まりの時刻（「13:15」）
+    pub fn label(&self, i: usize) -> String {
+        let time = self.start + chrono::Duration::minutes(self.minutes * i as i64);
+        time.format("%H:%M").to_string()
+    }
+}
+
+/// 時間ごとの玉数（味ごと）と注文数（黒い線）を描く
+pub fn draw_over_time(ui: &mut egui::Ui, data: &TimeSeries, style: TimeStyle) {
+    use egui_plotter::EguiBackend;
+    use plotters::prelude::*;
+
+    let len = data.orders.len();
+    let stacked = (0..len).map(|i| {
+        data.series
+            .iter()
+            .map(|series| series.counts[i])
+            .sum::<usize>()
+    });
+    let lines = data
+        .series
+        .iter()
+        .flat_map(|series| series.counts.iter().copied());
+    let max = match style {
+        TimeStyle::Stacked => stacked.max(),
+        TimeStyle::Lines => lines.max(),
+    }
+    .into_iter()
+    .chain(data.orders.iter().copied())
+    .max()
+    .unwrap_or(0);
+
+    let root = EguiBackend::new(ui).into_drawing_area();
+    let mut chart = ChartBuilder::on(&root)
+        .margin(5)
+        .x_label_area_size(30)
+        .y_label_area_size(30)
+        .build_cartesian_2d(0f64..len.max(1) as f64, 0f64..max as f64 + 1.0)
+        .unwrap();
+
+    chart
+        .configure_mesh()
+        .x_labels(len.clamp(1, 12))
+        .x_label_formatter(&|x| data.label(*x as usize))
+        .y_label_formatter(&|y| format!("{}", *y as usize))
+        .draw()
+        .unwrap();
+
+    let mut bottoms = vec![0; len];
+    for series in &data.series {
+        let [r, g, b] = series.color;
+        let color = RGBColor(r, g, b);
+
+        let drawn = match style {
+            TimeStyle::Stacked => chart
+                .draw_series(series.counts.iter().enumerate().map(|(i, n)| {
+                    let bottom = bottoms[i];
+                    bottoms[i] += n;
+                    let x = i as f64;
+                    Rectangle::new(
+                        [(x + 0.1, bottom as f64), (x + 0.9, bottoms[i] as f64)],
+                        color.filled(),
+                    )
+                }))
+                .unwrap(),
+            TimeStyle::Lines => chart
+                .draw_series(LineSeries::new(
+                    series
+                        .counts
+                        .iter()
+                        .enumerate()
+                        .map(|(i, n)| (i as f64 + 0.5, *n as f64)),
+                    color.stroke_width(2),
+                ))
+                .unwrap(),
+        };
+        drawn
+            .label(&series.name)
+            .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled()));
+    }
+
+    chart
+        .draw_series(LineSeries::new(
+            data.orders
+                .iter()
+                .enumerate()
+                .map(|(i, n)| (i as f64 + 0.5, *n as f64)),
+            BLACK.stroke_width(2),
+        ))
+        .unwrap()
+        .label("注文数")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 10, y)], BLACK.stroke_width(2)));
+
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()
+        .unwrap();
+
+    root.present().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::order;
+
+    #[test]
+    fn lengthens_buckets_for_long_sessions() {
+        // 締め忘れて5日続いたセッション
+        let orders = [order("2024-11-02T01:00:00Z"), order("2024-11-07T01:00:00Z")];
+        let data = TimeSeries::new(orders.iter(), &Menu::default(), 5, MAX_BUCKETS);
+
+        assert!(data.minutes > 60);
+        assert!(data.orders.len() <= MAX_BUCKETS);
+        assert_eq!(data.orders.iter().sum::<usize>(), 2);
+        assert_eq!(data.series[0].counts.iter().sum::<usize>(), 6);
+    }
+
+    #[test]
+    fn keeps_chosen_bucket_when_it_fits() {
+        let orders = [order("2024-11-02T01:00:00Z"), order("2024-11-02T03:00:00Z")];
+        let data = TimeSeries::new(orders.iter(), &Menu::default(), 15, MAX_BUCKETS);
+
+        assert_eq!(data.minutes, 15);
+        assert_eq!(data.orders.len(), 9);
+    }
+}
@@ -0,0 +1,260 @@
+//! 読み込んだ記録の食い違いを調べて直す
+//! 正しいのは `history` だけで、個数やグラフはそこから数える
+
+use crate::order::{Order, SoldFood};
+use crate::session::{self, Session};
+
+/// `history` の食い違いを直して、直したものを返す（空なら何も変えていない）
+/// セッションの番号を振り終えてから（`normalize_sessions` のあとに）呼ぶ
+pub fn repair_history(history: &mut Vec<Order>) -> Vec<String> {
+    let mut problems = vec![];
+
+    // 玉が1つも無い注文は数えようがないので消す
+    let before = history.len();
+    history.retain(|order| !matches!(order, Order::Food(order) if order.count() == 0));
+    if history.len() < before {
+        problems.push(format!("空の注文を{}件消しました", before - history.len()));
+    }
+
+    // 行の時刻は注文の時刻にそろえる
+    let mut retimed = 0;
+    for order in history.iter_mut() {
+        if let Order::Food(order) = order {
+            let time = order.time;
+            for line in order.lines.iter_mut().filter(|line| line.food.time != time) {
+                line.food.time = time;
+                retimed += 1;
+            }
+        }
+    }
+    if retimed > 0 {
+        problems.push(format!("注文と時刻の違う行を{}件直しました", retimed));
+    }
+
+    // 同じ番号のセッションがあれば、あとのほうに新しい番号を振る
+    let mut next_id = history
+        .iter()
+        .filter_map(|order| match order {
+            Order::Session(session) => Some(session.id),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let mut seen = vec![];
+    let mut renumbered = 0;
+    let mut current = 0;
+    for order in history.iter_mut() {
+        match order {
+            Order::Session(session) => {
+                if seen.contains(&session.id) {
+                    session.id = next_id;
+                    next_id += 1;
+                    renumbered += 1;
+                }
+                seen.push(session.id);
+                current = session.id;
+            }
+            Order::Food(order) => order.session = current,
+            Order::CashUp(_) => {}
+        }
+    }
+    if renumbered > 0 {
+        problems.push(format!(
+            "番号が重なっていたセッションを{}件振り直しました",
+            renumbered
+        ));
+    }
+
+    problems
+}
+
+/// 直しようがない食い違い（お釣りの間違い）を返す
+/// あとで修正した注文は、会計したときの金額で比べる
+pub fn check_change(history: &[Order]) -> Vec<String> {
+    let mut problems = vec![];
+    for order in history {
+        if let Order::Food(order) = order {
+            if let (Some(tendered), Some(change)) = (order.tendered, order.change) {
+                if tendered.checked_sub(order.charged_total()) != Some(change) {
+                    problems.push(format!(
+                        "{}の注文のお釣りが合いません（合計¥{} 預かり¥{} お釣り¥{}）",
+                        order
+                            .time
+                            .with_timezone(&chrono::Local)
+                            .format("%Y-%m-%d %H:%M"),
+                        order.charged_total(),
+                        tendered,
+                        change,
+                    ));
+                }
+            }
+        }
+    }
+
+    problems
+}
+
+/// 以前の `sold_food.json`（最後のリセットのあとに売れた玉）を、`history` から数えた玉と比べる
+/// 違っていたら、名前ごとの個数の違いを返す
+pub fn compare_sold_food(
+    sold_food: &[SoldFood],
+    from_history: impl Iterator<Item = SoldFood>,
+) -> Vec<String> {
+    // 名前、`sold_food.json` の個数、履歴の個数
+    let mut count: Vec<(String, usize, usize)> = vec![];
+    let mut add = |name: &str, file: usize, history: usize| {
+        if let Some((_, a, b)) = count.iter_mut().find(|(n, _, _)| n == name) {
+            *a += file;
+            *b += history;
+        } else {
+            count.push((name.to_owned(), file, history));
+        }
+    };
+
+    for food in sold_food {
+        add(&food.name, 1, 0);
+    }
+    for food in from_history {
+        add(&food.name, 0, 1);
+    }
+
+    let mismatched: Vec<String> = count
+        .into_iter()
+        .filter(|(_, file, history)| file != history)
+        .map(|(name, file, history)| {
+            format!("{}：sold_food.json は{}個、履歴は{}個", name, file, history)
+        })
+        .collect();
+    if mismatched.is_empty() {
+        return vec![];
+    }
+
+    let mut problems =
+        vec!["sold_food.json と履歴の個数が違うので、履歴の個数を使います".to_owned()];
+    problems.extend(mismatched);
+    problems
+}
+
+/// 以前の版のリセットは、空の `sold_food.json` を保存してから `Reset` を足していたので、
+/// リセットしてすぐに閉じると `history.json` の最後の `Reset` が抜けている
+/// `sold_food.json` が空なのに今のセッションに注文があれば、そのセッションを終えて true を返す
+/// `normalize_sessions` のあとに呼ぶ
+pub fn end_reset_session(sold_food: &[SoldFood], history: &mut Vec<Order>) -> bool {
+    let has_orders = session::sessions(history).last().is_some_and(|current| {
+        history[current.range.clone()]
+            .iter()
+            .any(|order| matches!(order, Order::Food(_)))
+    });
+    if !sold_food.is_empty() || !has_orders {
+        return false;
+    }
+
+    // 番号・名前・開始時刻（最後の注文の時刻）は `normalize_sessions` で振る
+    history.push(Order::Session(Session {
+        id: 0,
+        name: String::new(),
+        start: None,
+        float: 0,
+    }));
+    session::normalize_sessions(history);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 以前の版で、閉店のときにリセットしてすぐ閉じたときの2つのファイル
+    fn reset_then_quit() -> (Vec<SoldFood>, Vec<Order>) {
+        let sold_food = serde_json::from_slice(include_bytes!(
+            "../tests/golden/v0/reset_then_quit/sold_food.json"
+        ))
+        .unwrap();
+        let mut history = crate::schema::from_slice(include_bytes!(
+            "../tests/golden/v0/reset_then_quit/history.json"
+        ))
+        .unwrap();
+        session::normalize_sessions(&mut history);
+        (sold_food, history)
+    }
+
+    fn food_count(history: &[Order], range: std::ops::Range<usize>) -> usize {
+        history[range]
+            .iter()
+            .map(|order| match order {
+                Order::Food(order) => order.count(),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    #[test]
+    fn baseline_sold_food_matches_history() {
+        let sold_food: Vec<SoldFood> =
+            serde_json::from_slice(include_bytes!("../tests/golden/v0/sold_food.json")).unwrap();
+        let mut history: Vec<Order> =
+            crate::schema::from_slice(include_bytes!("../tests/golden/v0/history.json")).unwrap();
+        session::normalize_sessions(&mut history);
+
+        assert!(!end_reset_session(&sold_food, &mut history));
+        let current = session::sessions(&history).pop().unwrap().range;
+        let from_history = history[current].iter().flat_map(|order| match order {
+            Order::Food(order) => order.sold_food().collect(),
+            _ => vec![],
+        });
+        assert!(compare_sold_food(&sold_food, from_history).is_empty());
+    }
+
+    #[test]
+    fn reports_wrong_change_without_changing_history() {
+        let (_, mut history) = reset_then_quit();
+        if let Some(Order::Food(order)) = history.last_mut() {
+            order.lines[0].price = 300;
+            order.tendered = Some(500);
+            order.change = Some(100);
+        }
+
+        // 直すものは無いので、ログを書き直さない
+        assert!(repair_history(&mut history).is_empty());
+        assert_eq!(check_change(&history).len(), 1);
+    }
+
+    #[test]
+    fn ends_session_left_open_by_reset() {
+        let (sold_food, mut history) = reset_then_quit();
+        assert_eq!(session::sessions(&history).len(), 2);
+
+        assert!(end_reset_session(&sold_food, &mut history));
+
+        let sessions = session::sessions(&history);
+        assert_eq!(sessions.len(), 3);
+        // 前の日の注文はそのセッションに残り、新しい日は空から始まる
+        assert_eq!(food_count(&history, sessions[0].range.clone()), 3);
+        assert_eq!(food_count(&history, sessions[1].range.clone()), 4);
+        assert_eq!(food_count(&history, sessions[2].range.clone()), 0);
+        assert_eq!(
+            sessions[2].session.start.unwrap().to_rfc3339(),
+            "2024-11-03T08:30:00+00:00"
+        );
+        assert!(compare_sold_food(&sold_food, std::iter::empty()).is_empty());
+    }
+
+    #[test]
+    fn keeps_session_when_sold_food_has_orders() {
+        let (_, mut history) = reset_then_quit();
+        let sold_food: Vec<SoldFood> = history
+            .iter()
+            .skip_while(|order| !matches!(order, Order::Session(session) if session.id == 2))
+            .filter_map(|order| match order {
+                Order::Food(order) => Some(order.sold_food()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        assert_eq!(sold_food.len(), 4);
+
+        assert!(!end_reset_session(&sold_food, &mut history));
+        assert_eq!(session::sessions(&history).len(), 2);
+    }
+}
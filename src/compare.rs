@@ -0,0 +1,393 @@
+//! セッション（営業日）ごとの売れ方の比較
+//! この端末の記録と、書き出した記録ファイルのセッションを並べる
+
+use crate::cashup::SessionReport;
+use crate::menu::Menu;
+use crate::order::Order;
+use crate::session;
+
+/// 1つのセッションの集計
+pub struct Summary {
+    // どの記録のセッションか（この端末・読み込んだファイル）
+    pub source: String,
+    pub name: String,
+    pub orders: usize,
+    pub sold: usize,
+    pub revenue: u32,
+    // いちばん玉が売れた時（現地時刻の「時」）と、その時間の玉数
+    pub peak: Option<(u32, usize)>,
+    // 味ごとの玉数（メニューの順）
+    pub flavors: Vec<(String, usize)>,
+    // 比べる対象に入れる
+    pub selected: bool,
+}
+
+impl Summary {
+    /// 1回の注文の平均玉数
+    pub fn average(&self) -> f64 {
+        self.sold as f64 / self.orders.max(1) as f64
+    }
+
+    pub fn count_of(&self, flavor: &str) -> usize {
+        self.flavors
+            .iter()
+            .find(|(name, _)| name == flavor)
+            .map_or(0, |(_, n)| *n)
+    }
+}
+
+/// `history` をセッションごとに集計する（注文の無いセッションは除く）
+/// この端末の記録なら `menu` を渡し、名前を変えたメニューは番号で今の名前にまとめる
+/// メニューの番号は端末やフォルダごとに振るので、ほかの記録は記録した名前で数える
+pub fn summaries(history: &[Order], menu: Option<&Menu>, source: &str) -> Vec<Summary> {
+    session::sessions(history)
+        .into_iter()
+        .map(|range| {
+            let orders: Vec<_> = history[range.range.clone()]
+                .iter()
+                .filter_map(|order| match order {
+                    Order::Food(order) if !order.is_void() => Some(order),
+                    _ => None,
+                })
+                .collect();
+
+            let mut flavors: Vec<(String, usize)> = menu.map_or(vec![], |menu| {
+                menu.items
+                    .iter()
+                    .map(|item| (item.name.clone(), 0))
+                    .collect()
+            });
+            let mut hours: Vec<(u32, usize)> = vec![];
+            for order in &orders {
+                use chrono::Timelike as _;
+
+                let hour = order.time.with_timezone(&chrono::Local).hour();
+                match hours.iter_mut().find(|(h, _)| *h == hour) {
+                    Some((_, n)) => *n += order.count(),
+                    None => hours.push((hour, order.count())),
+                }
+
+                for line in &order.lines {
+                    let name = menu
+                        .and_then(|menu| menu.get(line.food.id))
+                        .map_or(line.food.name.as_str(), |item| item.name.as_str());
+                    match flavors.iter_mut().find(|(n, _)| n == name) {
+                        Some((_, n)) => *n += line.n,
+                        None => flavors.push((name.to_owned(), line.n)),
+                    }
+                }
+            }
+
+            Summary {
+                source: source.to_owned(),
+                name: range.session.name.clone(),
+                orders: orders.len(),
+                sold: orders.iter().map(|order| order.count()).sum(),
+                revenue: orders.iter().map(|order| order.total()).sum(),
+                peak: hours.into_iter().max_by_key(|(_, n)| *n),
+                flavors,
+                selected: false,
+            }
+        })
+        .filter(|summary| summary.orders > 0)
+        .collect()
+}
+
+/// レジ締めの集計（`reports/*.json`）を1つのセッションとして集計する
+/// 注文ごとの時刻は残っていないので、ピークは分からない
+pub fn report_summary(report: &SessionReport, source: &str) -> Summary {
+    Summary {
+        source: source.to_owned(),
+        name: report.session.name.clone(),
+        orders: report.orders,
+        sold: report.sold,
+        revenue: report.revenue,
+        peak: None,
+        flavors: report.sold_food_count.clone(),
+        selected: false,
+    }
+}
+
+/// セッションの比較ウィンドウ
+pub struct Dashboard {
+    pub summaries: Vec<Summary>,
+    // 読み込んだファイルの数（名前を付ける用）
+    files: usize,
+}
+
+impl Dashboard {
+    pub fn new(summaries: Vec<Summary>) -> Self {
+        Self {
+            summaries,
+            files: 0,
+        }
+    }
+
+    /// 書き出した記録ファイル（全部の記録か、レジ締めの集計）のセッションを足す
+    /// 以前の記録もあるので、この端末の記録と同じようにセッションを整えてから集計する
+    pub fn add_archive(&mut self, bytes: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let added = match crate::schema::from_slice::<SessionReport>(bytes) {
+            Ok(report) => vec![report_summary(&report, &self.source())],
+            Err(_) => {
+                let mut history: Vec<Order> = crate::schema::from_slice(bytes)?;
+                session::normalize_sessions(&mut history);
+                summaries(&history, None, &self.source())
+            }
+        };
+
+        self.files += 1;
+        self.summaries.extend(added);
+        Ok(())
+    }
+
+    // 次に読み込むファイルの名前
+    fn source(&self) -> String {
+        format!("ファイル{}", self.files + 1)
+    }
+
+    /// 記録ファイルを読むボタンが押されたら true
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let open = ui.button("記録ファイルを読み込む…").clicked();
+
+        ui.separator();
+
+        // 比べるセッションを選ぶ
+        egui::ScrollArea::vertical()
+            .id_salt("compare_sessions")
+            .max_height(120.0)
+            .show(ui, |ui| {
+                for summary in &mut self.summaries {
+                    let text = format!("{}（{}）", summary.name, summary.source);
+                    ui.checkbox(&mut summary.selected, text);
+                }
+            });
+
+        let selected: Vec<&Summary> = self
+            .summaries
+            .iter()
+            .filter(|summary| summary.selected)
+            .collect();
+        if selected.is_empty() {
+            ui.label("比べるセッションを選んでください");
+            return open;
+        }
+
+        // すべてのセッションの味（最初に出てきた順）
+        let mut flavors: Vec<&str> = vec![];
+        for summary in &selected {
+            for (name, _) in &summary.flavors {
+                if !flavors.contains(&name.as_str()) {
+                    flavors.push(name);
+                }
+            }
+        }
+
+        ui.separator();
+
+        let (rect, _) = ui.allocate_exact_size(
+            egui::vec2(ui.available_width(), 300.0),
+            egui::Sense::hover(),
+        );
+        ui.scope_builder(egui::UiBuilder::new().max_rect(rect), |ui| {
+            draw_bars(ui, &selected, &flavors);
+        });
+
+        ui.separator();
+
+        table_ui(ui, &selected, &flavors);
+
+        open
+    }
+}
+
+// セッションの色（グラフと表の見出しで同じ色）
+fn color(i: usize) -> [u8; 3] {
+    use plotters::style::{Color as _, Palette as _, Palette99};
+
+    let (r, g, b) = Palette99::pick(i).rgb();
+    [r, g, b]
+}
+
+// 味ごとに、セッションの玉数を並べた棒を描く
+fn draw_bars(ui: &mut egui::Ui, selected: &[&Summary], flavors: &[&str]) {
+    use egui_plotter::EguiBackend;
+    use plotters::prelude::*;
+
+    // 味ごとにセッションの数 + 1（すき間）の区画を使う
+    let group = selected.len() as u32 + 1;
+    let max = selected
+        .iter()
+        .flat_map(|summary| summary.flavors.iter().map(|(_, n)| *n))
+        .max()
+        .unwrap_or(0);
+
+    let root = EguiBackend::new(ui).into_drawing_area();
+    let mut chart = ChartBuilder::on(&root)
+        .margin(5)
+        .x_label_area_size(30)
+        .y_label_area_size(40)
+        .build_cartesian_2d(
+            (0u32..flavors.len() as u32 * group).into_segmented(),
+            0usize..max + 1,
+        )
+        .unwrap();
+
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels((flavors.len() * group as usize).max(1))
+        .x_label_formatter(&|x| match x {
+            // 味の名前は区画の真ん中に出す
+            SegmentValue::CenterOf(k) if k % group == (group - 1) / 2 => flavors
+                .get((k / group) as usize)
+                .map_or(String::new(), |name| name.to_string()),
+            _ => String::new(),
+        })
+        .draw()
+        .unwrap();
+
+    for (j, summary) in selected.iter().enumerate() {
+        let [r, g, b] = color(j);
+        chart
+            .draw_series(
+                Histogram::vertical(&chart)
+                    .margin(2)
+                    .style(RGBColor(r, g, b).filled())
+                    .data(
+                        flavors
+                            .iter()
+                            .enumerate()
+                            .map(|(i, name)| (i as u32 * group + j as u32, summary.count_of(name))),
+                    ),
+            )
+            .unwrap();
+    }
+
+    root.present().unwrap();
+}
+
+// セッションごとの数字と、最初と最後のセッションの差
+fn table_ui(ui: &mut egui::Ui, selected: &[&Summary], flavors: &[&str]) {
+    let (first, last) = (selected[0], selected[selected.len() - 1]);
+    let difference = selected.len() > 1;
+
+    let signed = |a: f64, b: f64, decimals: usize| format!("{:+.*}", decimals, b - a);
+    let peak = |summary: &Summary| {
+        summary
+            .peak
+            .map_or("-".to_owned(), |(hour, n)| format!("{}時（{}玉）", hour, n))
+    };
+
+    egui::Grid::new("compare_grid")
+        .num_columns(selected.len() + 2)
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("");
+            for (j, summary) in selected.iter().enumerate() {
+                let [r, g, b] = color(j);
+                ui.colored_label(egui::Color32::from_rgb(r, g, b), &summary.name);
+            }
+            if difference {
+                ui.label("差（最後−最初）");
+            }
+            ui.end_row();
+
+            let row = |ui: &mut egui::Ui,
+                       label: &str,
+                       value: &dyn Fn(&Summary) -> String,
+                       number: &dyn Fn(&Summary) -> f64,
+                       decimals: usize| {
+                ui.label(label);
+                for summary in selected {
+                    ui.label(value(summary));
+                }
+                if difference {
+                    ui.label(signed(number(first), number(last), decimals));
+                }
+                ui.end_row();
+            };
+
+            for flavor in flavors {
+                row(
+                    ui,
+                    flavor,
+                    &|summary| format!("{}玉", summary.count_of(flavor)),
+                    &|summary| summary.count_of(flavor) as f64,
+                    0,
+                );
+            }
+            row(
+                ui,
+                "合計",
+                &|summary| format!("{}玉", summary.sold),
+                &|summary| summary.sold as f64,
+                0,
+            );
+            row(
+                ui,
+                "注文数",
+                &|summary| format!("{}件", summary.orders),
+                &|summary| summary.orders as f64,
+                0,
+            );
+            row(
+                ui,
+                "売上",
+                &|summary| format!("¥{}", summary.revenue),
+                &|summary| summary.revenue as f64,
+                0,
+            );
+            row(
+                ui,
+                "平均玉数",
+                &|summary| format!("{:.1}玉", summary.average()),
+                &Summary::average,
+                1,
+            );
+
+            ui.label("ピーク");
+            for summary in selected {
+                ui.label(peak(summary));
+            }
+            if difference {
+                ui.label("");
+            }
+            ui.end_row();
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_archived_lines_by_recorded_name() {
+        // ほかの端末では、この端末の「チョコ」と同じ番号が「プレーン」だった
+        let mut order = crate::test_util::order("2024-11-02T01:00:00Z");
+        order.lines[0].food.id = 2;
+        let history = vec![Order::Food(order)];
+        let bytes = crate::schema::to_string_pretty(&history).unwrap();
+
+        let mut dashboard = Dashboard::new(vec![]);
+        dashboard.add_archive(bytes.as_bytes()).unwrap();
+
+        let summary = &dashboard.summaries[0];
+        assert_eq!(summary.source, "ファイル1");
+        assert_eq!(summary.count_of("プレーン"), 3);
+        assert_eq!(summary.flavors.len(), 1);
+    }
+
+    #[test]
+    fn reads_cash_up_reports() {
+        let mut dashboard = Dashboard::new(vec![]);
+        dashboard
+            .add_archive(include_bytes!("../tests/golden/v0/report.json"))
+            .unwrap();
+
+        let summary = &dashboard.summaries[0];
+        assert_eq!(summary.name, "2024-11-02");
+        assert_eq!((summary.orders, summary.sold, summary.revenue), (2, 7, 700));
+        assert_eq!(summary.count_of("チョコ"), 3);
+        assert!(summary.peak.is_none());
+    }
+}
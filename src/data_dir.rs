@@ -0,0 +1,89 @@
+//! データを置くフォルダ
+
+use std::path::{Path, PathBuf};
+
+/// データフォルダを指定する環境変数
+pub const ENV_VAR: &str = "FOOD_COUNTER_DATA_DIR";
+
+/// `--data-dir <path>`、環境変数 `FOOD_COUNTER_DATA_DIR`、前に選んだフォルダ（`saved`）、
+/// OS のデータフォルダの順に決める
+/// OS のデータフォルダに記録が無く、起動したフォルダに以前の版の記録があれば、起動したフォルダを使う
+pub fn resolve(saved: Option<PathBuf>) -> PathBuf {
+    from_args(std::env::args().skip(1))
+        .or_else(|| std::env::var_os(ENV_VAR).map(PathBuf::from))
+        .or(saved)
+        .unwrap_or_else(|| default_dir(eframe::storage_dir("food_counter"), PathBuf::from(".")))
+}
+
+// `--data-dir <path>` と `--data-dir=<path>` のどちらでも受け付ける
+fn from_args(mut args: impl Iterator<Item = String>) -> Option<PathBuf> {
+    while let Some(arg) = args.next() {
+        if arg == "--data-dir" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--data-dir=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+
+    None
+}
+
+// 以前の版は起動したフォルダで読み書きしていたので、更新してすぐに記録が空に見えないようにする
+fn default_dir(platform: Option<PathBuf>, launch: PathBuf) -> PathBuf {
+    match platform {
+        Some(platform) if has_orders(&platform) || !has_orders(&launch) => platform,
+        _ => launch,
+    }
+}
+
+// 注文の記録があるフォルダか（今のログか、以前の版のファイル）
+fn has_orders(dir: &Path) -> bool {
+    ["events.jsonl", "history.json", "sold_food.json"]
+        .iter()
+        .any(|name| dir.join(name).exists())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn reads_data_dir_argument() {
+        let args = ["--data-dir", "a"].map(str::to_owned);
+        assert_eq!(from_args(args.into_iter()), Some(PathBuf::from("a")));
+
+        let args = ["--data-dir=b"].map(str::to_owned);
+        assert_eq!(from_args(args.into_iter()), Some(PathBuf::from("b")));
+    }
+
+    #[test]
+    fn keeps_using_launch_folder_with_old_data() {
+        let dir = temp_dir("launch_folder");
+        let (platform, launch) = (dir.join("platform"), dir.join("launch"));
+        std::fs::create_dir_all(&platform).unwrap();
+        std::fs::create_dir_all(&launch).unwrap();
+
+        // OS のフォルダも起動したフォルダも空なら OS のフォルダ
+        assert_eq!(
+            default_dir(Some(platform.clone()), launch.clone()),
+            platform
+        );
+
+        // 以前の版の記録が起動したフォルダにだけあれば、そちら
+        std::fs::write(launch.join("history.json"), "[]").unwrap();
+        std::fs::write(launch.join("sold_food.json"), "[]").unwrap();
+        assert_eq!(default_dir(Some(platform.clone()), launch.clone()), launch);
+
+        // OS のフォルダに記録ができたら、OS のフォルダ
+        std::fs::write(platform.join("events.jsonl"), "").unwrap();
+        assert_eq!(
+            default_dir(Some(platform.clone()), launch.clone()),
+            platform
+        );
+
+        assert_eq!(default_dir(None, launch.clone()), launch);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,541 @@
+use crate::order::Order;
+
+/// `history` への変更1回分
+/// `events.jsonl` に1行ずつ追記して、起動時に最初から適用し直す
+/// ファイルの先頭の行には形式の番号（`{"version": 1}`）を書く
+#[derive(serde::Deserialize, serde::Serialize)]
+pub enum Event {
+    // 注文・レジ締め・セッションの開始を追加する
+    Push(Order),
+    // 最後の記録を取り消す
+    Pop,
+    // 会計の結果を注文に記録する
+    Paid {
+        order: OrderRef,
+        tendered: u32,
+        change: u32,
+    },
+    // 注文の味や個数を直す（修正前の行も残す）
+    Amend {
+        order: OrderRef,
+        amendment: crate::amend::Amendment,
+    },
+    // 注文を無効にする（記録は残す）
+    Void {
+        order: OrderRef,
+        time: chrono::DateTime<chrono::Utc>,
+        reason: String,
+    },
+}
+
+/// 記録が指す注文
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy)]
+pub enum OrderRef {
+    // 注文の時刻（注文ごとに違う）
+    Time(chrono::DateTime<chrono::Utc>),
+    // 以前のログの `history` 上の位置
+    // 読み込んだあとに足したセッションの分だけずれることがあるので、新しくは書かない
+    Index(usize),
+}
+
+impl OrderRef {
+    fn find(self, history: &mut [Order]) -> Option<&mut crate::order::FoodOrder> {
+        match self {
+            OrderRef::Time(time) => history.iter_mut().find_map(|order| match order {
+                Order::Food(order) if order.time == time => Some(order),
+                _ => None,
+            }),
+            OrderRef::Index(index) => match history.get_mut(index) {
+                Some(Order::Food(order)) => Some(order),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl crate::schema::Versioned for Event {
+    const MIGRATIONS: &'static [crate::schema::Migration] =
+        &[crate::schema::unchanged, order_ref_from_index];
+}
+
+// 形式 1 → 2
+// 注文を `history` 上の位置（`"index": 3`）で指していたのを、`"order": {"Index": 3}` にする
+fn order_ref_from_index(
+    mut event: serde_json::Value,
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    for name in ["Paid", "Amend", "Void"] {
+        if let Some(fields) = event
+            .get_mut(name)
+            .and_then(|fields| fields.as_object_mut())
+        {
+            let index = fields.remove("index").ok_or("Missing order index")?;
+            fields.insert("order".to_owned(), serde_json::json!({ "Index": index }));
+        }
+    }
+
+    Ok(event)
+}
+
+impl Event {
+    pub fn apply(self, history: &mut Vec<Order>) {
+        match self {
+            Event::Push(order) => history.push(order),
+            Event::Pop => {
+                history.pop();
+            }
+            Event::Paid {
+                order,
+                tendered,
+                change,
+            } => {
+                if let Some(order) = order.find(history) {
+                    order.tendered = Some(tendered);
+                    order.change = Some(change);
+                }
+            }
+            Event::Amend { order, amendment } => {
+                if let Some(order) = order.find(history) {
+                    order.lines = amendment.after.clone();
+                    order.amendments.push(amendment);
+                }
+            }
+            Event::Void {
+                order,
+                time,
+                reason,
+            } => {
+                if let Some(order) = order.find(history) {
+                    order.void = Some(crate::void::Void { time, reason });
+                }
+            }
+        }
+    }
+}
+
+/// 1行追記して、ディスクに書き込まれるまで待つ
+/// 操作の記録（`audit.jsonl`）も同じように追記する
+pub fn append<T: crate::schema::Versioned>(
+    path: &std::path::Path,
+    value: &T,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write as _;
+
+    let mut line = serde_json::to_string(value)?;
+    line.push('\n');
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    // 新しいファイルには形式の番号から書く
+    if file.metadata()?.len() == 0 {
+        line = crate::schema::header::<T>()? + "\n" + &line;
+    } else {
+        // 前の形式のファイルに足すと、読むときに今の形式の行まで変換されてしまう
+        // 読み込むときに今の形式で書き直しているので、ここで違うのは他のアプリが書いたとき
+        let version = file_version(path)?;
+        if version != T::version() {
+            return Err(format!(
+                "Unexpected log version: {}: {} (expected {})",
+                path.display(),
+                version,
+                T::version()
+            )
+            .into());
+        }
+    }
+    file.write_all(line.as_bytes())?;
+    file.sync_data()?;
+
+    Ok(())
+}
+
+// ファイルの先頭の行の形式の番号（番号を書く前のファイルは 0）
+fn file_version(path: &std::path::Path) -> Result<u32, Box<dyn std::error::Error>> {
+    use std::io::BufRead as _;
+
+    let mut first = String::new();
+    std::io::BufReader::new(std::fs::File::open(path)?).read_line(&mut first)?;
+
+    Ok(crate::schema::parse_header(&first).unwrap_or(0))
+}
+
+/// 全部の記録を書き出す（ログを新しく作るとき用）
+/// 書き終わるまでは前のファイルを残しておく
+pub fn write_all(
+    path: &std::path::Path,
+    history: &[Order],
+) -> Result<(), Box<dyn std::error::Error>> {
+    // `Event` に入れるために複製する
+    let events: Vec<Event> = history
+        .iter()
+        .map(|order| Event::Push(order.clone()))
+        .collect();
+
+    rewrite(path, &events)
+}
+
+/// 今の形式の番号を付けて、1行ずつのファイルを書き直す
+pub fn rewrite<T: crate::schema::Versioned>(
+    path: &std::path::Path,
+    values: &[T],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut text = crate::schema::header::<T>()?;
+    text.push('\n');
+    for value in values {
+        text += &serde_json::to_string(value)?;
+        text.push('\n');
+    }
+
+    crate::file::write_atomic(path, text.as_bytes())
+}
+
+/// ログを読んだ結果
+pub struct Replay {
+    pub history: Vec<Order>,
+    // 先頭の行の形式の番号（番号の無いログは、番号を付ける前の形式 0）
+    pub version: u32,
+    // 適用できた記録の数
+    pub events: usize,
+    // 読めた行の終わりまでのバイト数
+    pub valid_len: usize,
+    // 最初に読めなかった行の番号（1から）とエラー
+    pub broken: Option<(usize, String)>,
+    // 読めなかったのが最後の行だった（書き込み途中で落ちた）
+    pub broken_last: bool,
+    // 最後の行に改行が無い
+    pub missing_newline: bool,
+}
+
+/// ログを最初から適用して `history` を作る
+/// ファイルは変えない（バックアップを調べるときにも使う）
+/// 読めない行があれば、その前までの記録を返す
+pub fn replay(path: &std::path::Path) -> Result<Replay, Box<dyn std::error::Error>> {
+    read(&std::fs::read(path)?)
+}
+
+fn read(bytes: &[u8]) -> Result<Replay, Box<dyn std::error::Error>> {
+    // 読めない形式なら、どの行も使わずにやめる
+    let (version, lines) = read_lines::<Event>(bytes)?;
+    let mut replay = Replay {
+        history: vec![],
+        version,
+        events: 0,
+        valid_len: 0,
+        broken: None,
+        broken_last: false,
+        missing_newline: !bytes.is_empty() && !bytes.ends_with(b"\n"),
+    };
+
+    let count = lines.len();
+    for (i, line) in lines.into_iter().enumerate() {
+        match line.value {
+            Ok(Some(event)) => {
+                event.apply(&mut replay.history);
+                replay.events += 1;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                replay.broken = Some((line.number, e.to_string()));
+                replay.broken_last = i + 1 == count;
+                return Ok(replay);
+            }
+        }
+        replay.valid_len += line.bytes.len();
+    }
+
+    Ok(replay)
+}
+
+/// 1行ずつのファイルの1行
+pub struct Line<'a, T> {
+    // 行の番号（1から）
+    pub number: usize,
+    // 改行を含む行の中身
+    pub bytes: &'a [u8],
+    // 今の形式にした記録（形式の番号の行と空行は None）
+    pub value: Result<Option<T>, Box<dyn std::error::Error>>,
+}
+
+/// 1行ずつのファイル（`events.jsonl`・`audit.jsonl`）を行ごとに読む
+/// 先頭の行の形式の番号（番号の無いファイルは 0）も返す
+/// 読めない形式のファイルならエラーにする
+pub fn read_lines<T: crate::schema::Versioned>(
+    bytes: &[u8],
+) -> Result<(u32, Vec<Line<'_, T>>), Box<dyn std::error::Error>> {
+    let mut version = 0;
+    let mut lines = vec![];
+
+    for (i, bytes) in bytes.split_inclusive(|b| *b == b'\n').enumerate() {
+        let text = std::str::from_utf8(bytes);
+        if i == 0 {
+            if let Some(header) = text.ok().and_then(crate::schema::parse_header) {
+                crate::schema::check_version::<T>(header)?;
+                version = header;
+                lines.push(Line {
+                    number: 1,
+                    bytes,
+                    value: Ok(None),
+                });
+                continue;
+            }
+        }
+
+        let value = match text {
+            Ok(text) if text.trim().is_empty() => Ok(None),
+            Ok(text) => serde_json::from_str::<serde_json::Value>(text)
+                .map_err(Into::into)
+                .and_then(|value| crate::schema::migrate(version, value))
+                .map(Some),
+            Err(e) => Err(e.into()),
+        };
+        lines.push(Line {
+            number: i + 1,
+            bytes,
+            value,
+        });
+    }
+
+    Ok((version, lines))
+}
+
+/// 改行を書く前に落ちていたら、次の行がつながらないように改行だけ足しておく
+pub fn end_with_newline(path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write as _;
+
+    let mut file = std::fs::OpenOptions::new().append(true).open(path)?;
+    file.write_all(b"\n")?;
+    file.sync_data()?;
+
+    Ok(())
+}
+
+/// `events.jsonl` を読み、壊れていたら直す
+/// 直したことの説明も返す（読み込みの確認に出す）
+///
+/// - 書き込み途中で落ちた最後の行は、ログから切り詰める
+/// - 途中の行が読めなければ、元のファイルを残してから、その前までの記録と
+///   新しいバックアップのうち、多くの記録を読めたほうでログを書き直す
+pub fn load(
+    path: &std::path::Path,
+) -> Result<(Vec<Order>, Vec<String>), Box<dyn std::error::Error>> {
+    let live = replay(path)?;
+    let mut problems = vec![];
+
+    match live.broken.clone() {
+        None => {
+            if live.missing_newline {
+                end_with_newline(path)?;
+            }
+        }
+        Some((line, e)) if live.broken_last => {
+            eprintln!(
+                "Skipped broken last line of {}:{}: {}",
+                path.display(),
+                line,
+                e
+            );
+            std::fs::OpenOptions::new()
+                .write(true)
+                .open(path)?
+                .set_len(live.valid_len as u64)?;
+            problems.push(format!(
+                "{}の最後の行（{}行目）が書き込み途中だったので消しました",
+                path.display(),
+                line
+            ));
+        }
+        Some((line, e)) => {
+            eprintln!("Failed to load file: {}:{}: {}", path.display(), line, e);
+            let kept = crate::file::keep_corrupt(path)?;
+
+            // バックアップはログの前の状態なので、途中で切れたログより多く読めることがある
+            let mut best = live;
+            let mut source = None;
+            for backup in crate::file::backups(path)? {
+                match replay(&backup) {
+                    Ok(restored) if restored.broken.is_none() || restored.broken_last => {
+                        if restored.events > best.events {
+                            best = restored;
+                            source = Some(backup);
+                        }
+                        break;
+                    }
+                    Ok(_) => eprintln!("Failed to load file: {}", backup.display()),
+                    Err(e) => eprintln!("Failed to load file: {}: {}", backup.display(), e),
+                }
+            }
+
+            problems.push(format!(
+                "{}の{}行目が読めませんでした（元のファイルは{}に残しました）",
+                path.display(),
+                line,
+                kept.display()
+            ));
+            problems.push(match &source {
+                Some(backup) => format!("バックアップ{}から読みました", backup.display()),
+                None => format!("{}行目より前の記録を使います", line),
+            });
+
+            write_all(path, &best.history)?;
+            return Ok((best.history, problems));
+        }
+    }
+
+    // 前の形式のログは、今の形式で書き直す（このあとの追記と形式をそろえる）
+    if live.version < <Event as crate::schema::Versioned>::version() {
+        write_all(path, &live.history)?;
+    }
+
+    Ok((live.history, problems))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::Session;
+    use crate::test_util::{food, temp_dir};
+
+    #[test]
+    fn finds_order_by_time_after_sessions_are_inserted() {
+        let mut history = vec![food("2024-11-02T01:00:00Z"), food("2024-11-02T02:00:00Z")];
+        // 読み込んだあとに、ログに無いセッションが先頭に入る
+        history.insert(
+            0,
+            Order::Session(Session {
+                id: 1,
+                name: String::new(),
+                start: None,
+                float: 0,
+            }),
+        );
+
+        Event::Paid {
+            order: OrderRef::Time("2024-11-02T01:00:00Z".parse().unwrap()),
+            tendered: 500,
+            change: 200,
+        }
+        .apply(&mut history);
+
+        assert!(matches!(&history[1], Order::Food(order) if order.tendered == Some(500)));
+        assert!(matches!(&history[2], Order::Food(order) if order.tendered.is_none()));
+    }
+
+    #[test]
+    fn migrates_index_to_order_ref() {
+        let line = r#"{"Void":{"index":2,"time":"2024-11-02T03:00:00Z","reason":"x"}}"#;
+        let event: Event = crate::schema::migrate(1, serde_json::from_str(line).unwrap()).unwrap();
+
+        assert!(matches!(
+            event,
+            Event::Void {
+                order: OrderRef::Index(2),
+                ..
+            }
+        ));
+    }
+
+    fn log_of(history: &[Order]) -> String {
+        let mut text = crate::schema::header::<Event>().unwrap() + "\n";
+        for order in history {
+            text += &serde_json::to_string(&Event::Push(order.clone())).unwrap();
+            text.push('\n');
+        }
+        text
+    }
+
+    #[test]
+    fn truncates_broken_last_line() {
+        let dir = temp_dir("broken_last");
+        let path = dir.join("events.jsonl");
+        let good = log_of(&[food("2024-11-02T01:00:00Z"), food("2024-11-02T02:00:00Z")]);
+        std::fs::write(&path, good.clone() + r#"{"Push":{"Fo"#).unwrap();
+
+        let (history, problems) = load(&path).unwrap();
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), good);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_lines_before_broken_middle_line() {
+        let dir = temp_dir("broken_middle");
+        let path = dir.join("events.jsonl");
+        let good = log_of(&[food("2024-11-02T01:00:00Z"), food("2024-11-02T02:00:00Z")]);
+        let broken = good.clone() + "{broken}\n" + &log_of(&[food("2024-11-02T03:00:00Z")]);
+        std::fs::write(&path, &broken).unwrap();
+
+        let (history, _) = load(&path).unwrap();
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), good);
+        // 元のファイルは別の名前で残る
+        let kept: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().contains(".corrupt-"))
+            .collect();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(std::fs::read_to_string(kept[0].path()).unwrap(), broken);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn leaves_backups_untouched_when_log_is_corrupt() {
+        let dir = temp_dir("corrupt_with_backups");
+        let path = dir.join("events.jsonl");
+        let backups = dir.join("backups");
+        std::fs::create_dir_all(&backups).unwrap();
+        // 新しいバックアップは最後の行が書き込み途中、古いほうは読める
+        let newer = backups.join("events-20241102-010000-000.jsonl");
+        let older = backups.join("events-20241102-000000-000.jsonl");
+        let newer_text = log_of(&[
+            food("2024-11-02T01:00:00Z"),
+            food("2024-11-02T02:00:00Z"),
+            food("2024-11-02T03:00:00Z"),
+        ]) + r#"{"Pu"#;
+        let older_text = log_of(&[food("2024-11-02T01:00:00Z")]);
+        std::fs::write(&newer, &newer_text).unwrap();
+        std::fs::write(&older, &older_text).unwrap();
+        std::fs::write(
+            &path,
+            log_of(&[food("2024-11-02T01:00:00Z")]) + "{broken}\n",
+        )
+        .unwrap();
+
+        let (history, problems) = load(&path).unwrap();
+
+        // ログより多く読めたバックアップを使う
+        assert_eq!(history.len(), 3);
+        assert_eq!(problems.len(), 2);
+        assert_eq!(std::fs::read_to_string(&newer).unwrap(), newer_text);
+        assert_eq!(std::fs::read_to_string(&older).unwrap(), older_text);
+        assert_eq!(replay(&path).unwrap().history.len(), 3);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rewrites_old_log_before_appending() {
+        let dir = temp_dir("old_version");
+        let path = dir.join("events.jsonl");
+        let push = serde_json::to_string(&Event::Push(food("2024-11-02T01:00:00Z"))).unwrap();
+        let paid = r#"{"Paid":{"index":0,"tendered":500,"change":200}}"#;
+        std::fs::write(&path, format!("{{\"version\":1}}\n{}\n{}\n", push, paid)).unwrap();
+
+        // 前の形式のままでは足さない
+        assert!(append(&path, &Event::Pop).is_err());
+
+        let (history, _) = load(&path).unwrap();
+        assert!(matches!(&history[0], Order::Food(order) if order.tendered == Some(500)));
+        assert_eq!(
+            replay(&path).unwrap().version,
+            <Event as crate::schema::Versioned>::version()
+        );
+
+        append(&path, &Event::Push(food("2024-11-02T02:00:00Z"))).unwrap();
+        assert_eq!(replay(&path).unwrap().history.len(), 2);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
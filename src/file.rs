@@ -0,0 +1,220 @@
+//! ファイルの安全な書き込みとバックアップ
+
+use std::path::{Path, PathBuf};
+
+use crate::schema::Versioned;
+
+/// ファイルごとに残しておくバックアップの数
+pub const BACKUP_COUNT: usize = 10;
+
+/// 一時ファイルに書いてから名前を変えて置き換える
+/// 途中で落ちても、元のファイルか新しいファイルのどちらかが残る
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write as _;
+
+    let tmp = with_suffix(path, ".tmp");
+    {
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+    }
+    std::fs::rename(&tmp, path)?;
+
+    Ok(())
+}
+
+/// 今のファイルをバックアップしてから書き込む
+pub fn save_with_backup(path: &Path, bytes: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    backup(path)?;
+    write_atomic(path, bytes)
+}
+
+/// 形式の番号を付けた JSON にして、前のファイルを `backups/` に残してから置き換える
+pub fn save_json<T: Versioned>(path: &Path, value: &T) -> Result<(), Box<dyn std::error::Error>> {
+    save_with_backup(path, crate::schema::to_string_pretty(value)?.as_bytes())
+}
+
+/// 形式の番号を見て JSON を読む
+/// ファイルが無ければ既定の値を書き出して、それを使う
+/// 壊れていたら新しいバックアップから読む
+pub fn load_json<T: Versioned + Default>(path: &Path) -> Result<T, Box<dyn std::error::Error>> {
+    if !std::fs::exists(path)? {
+        let value = T::default();
+        save_json(path, &value)?;
+        return Ok(value);
+    }
+
+    load_with_fallback(path, |path| {
+        crate::schema::from_slice(&std::fs::read(path)?)
+    })
+}
+
+/// 今のファイルを `backups/` に日時付きでコピーし、古いものを消す
+pub fn backup(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if !std::fs::exists(path)? {
+        return Ok(());
+    }
+
+    let dir = backup_dir(path);
+    std::fs::create_dir_all(&dir)?;
+
+    let (stem, ext) = stem_and_extension(path);
+    let time = chrono::Local::now().format("%Y%m%d-%H%M%S-%3f");
+    std::fs::copy(path, dir.join(format!("{}-{}{}", stem, time, ext)))?;
+
+    for old in backups(path)?.into_iter().skip(BACKUP_COUNT) {
+        std::fs::remove_file(old)?;
+    }
+
+    Ok(())
+}
+
+/// `path` のバックアップを新しい順に返す
+pub fn backups(path: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let dir = backup_dir(path);
+    if !std::fs::exists(&dir)? {
+        return Ok(vec![]);
+    }
+
+    let (stem, ext) = stem_and_extension(path);
+    let prefix = format!("{}-", stem);
+
+    let mut list: Vec<PathBuf> = std::fs::read_dir(&dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|backup| {
+            backup
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(&prefix))
+                .and_then(|rest| rest.strip_suffix(&ext))
+                .is_some_and(|time| time.chars().all(|c| c.is_ascii_digit() || c == '-'))
+        })
+        .collect();
+
+    // 日時は桁をそろえているので、名前の順が時刻の順になる
+    list.sort();
+    list.reverse();
+
+    Ok(list)
+}
+
+/// 読み込みに失敗したら、新しいバックアップから順に試す
+/// バックアップで読めたら、壊れたファイルは `keep_corrupt` で残し、バックアップで置き換える
+/// 新しいアプリで保存されたファイルは壊れていないので、置き換えずにエラーにする
+pub fn load_with_fallback<T>(
+    path: &Path,
+    load: impl Fn(&Path) -> Result<T, Box<dyn std::error::Error>>,
+) -> Result<T, Box<dyn std::error::Error>> {
+    let error = match load(path) {
+        Ok(value) => return Ok(value),
+        Err(e) => e,
+    };
+    if error.is::<crate::schema::UnsupportedVersion>() {
+        return Err(error);
+    }
+    eprintln!("Failed to load file: {}: {}", path.display(), error);
+
+    for backup in backups(path)? {
+        match load(&backup) {
+            Ok(value) => {
+                eprintln!("Restored {} from {}", path.display(), backup.display());
+                keep_corrupt(path)?;
+                write_atomic(path, &std::fs::read(&backup)?)?;
+                return Ok(value);
+            }
+            Err(e) => eprintln!("Failed to load file: {}: {}", backup.display(), e),
+        }
+    }
+
+    Err(error)
+}
+
+/// 壊れたファイルを、日時を付けた `.corrupt-…` として隣に残す
+/// 次に壊れたときに上書きしないように、毎回別の名前にする
+pub fn keep_corrupt(path: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let time = chrono::Local::now().format("%Y%m%d-%H%M%S-%3f");
+    let kept = with_suffix(path, &format!(".corrupt-{}", time));
+    std::fs::copy(path, &kept)?;
+
+    Ok(kept)
+}
+
+fn backup_dir(path: &Path) -> PathBuf {
+    path.parent().unwrap_or(Path::new("")).join("backups")
+}
+
+// "menu.json" なら ("menu", ".json")
+fn stem_and_extension(path: &Path) -> (String, String) {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let ext = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+
+    (stem, ext)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::menu::Menu;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn keeps_newest_backups_only() {
+        let dir = temp_dir("backup_rotation");
+        let path = dir.join("menu.json");
+        for i in 0..BACKUP_COUNT + 3 {
+            std::fs::write(&path, i.to_string()).unwrap();
+            backup(&path).unwrap();
+            // バックアップの名前はミリ秒まで
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+
+        let list = backups(&path).unwrap();
+        assert_eq!(list.len(), BACKUP_COUNT);
+        assert_eq!(
+            std::fs::read_to_string(&list[0]).unwrap(),
+            (BACKUP_COUNT + 2).to_string()
+        );
+        assert_eq!(
+            std::fs::read_to_string(&list[BACKUP_COUNT - 1]).unwrap(),
+            "3"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_backup_when_corrupt() {
+        let dir = temp_dir("load_fallback");
+        let path = dir.join("menu.json");
+        let mut menu = Menu::default();
+        menu.items.truncate(2);
+        save_json(&path, &menu).unwrap();
+        // 2回目の保存で、1回目のファイルがバックアップになる
+        save_json(&path, &menu).unwrap();
+        std::fs::write(&path, r#"{"version": 1, "data": {"ite"#).unwrap();
+
+        let loaded: Menu = load_json(&path).unwrap();
+
+        assert_eq!(loaded.items.len(), 2);
+        // 壊れたファイルは残し、読めたバックアップで置き換える
+        let kept = std::fs::read_dir(&dir)
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().contains(".corrupt-"))
+            .count();
+        assert_eq!(kept, 1);
+        let again: Menu = crate::schema::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(again.items.len(), 2);
+    }
+}
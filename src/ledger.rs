@@ -0,0 +1,428 @@
+//! 記録の書き出しと読み込み（別の端末とのやりとり用）
+
+use crate::menu::Menu;
+use crate::order::{Order, OrderLine};
+use crate::session::Session;
+
+/// 読み込んだファイルの中身を `update` に渡す入れ物
+/// web 版ではファイルを選ぶのを待てないので、読めたらここに入れる
+pub type Inbox = std::sync::Arc<std::sync::Mutex<Option<Vec<u8>>>>;
+
+// Excel で日本語の名前が文字化けしないように、CSV の先頭に付ける
+const BOM: &str = "\u{feff}";
+
+/// 注文の行ごとに1行の CSV にする
+/// 受け取った金額とお釣りは注文全体のものなので、注文の最初の行にだけ入れる
+/// （列を足し合わせても二重に数えないように）
+pub fn to_csv(history: &[Order]) -> String {
+    let mut csv = String::from(
+        "session,order,time,item_id,item,count,price,payment_method,tendered,change,void,void_reason,staff\n",
+    );
+
+    let mut session = "";
+    let mut number = 0;
+    for order in history {
+        match order {
+            Order::Session(s) => session = &s.name,
+            Order::Food(order) => {
+                number += 1;
+                let time = order.time.with_timezone(&chrono::Local);
+                for (i, line) in order.lines.iter().enumerate() {
+                    let first = i == 0;
+                    let row = [
+                        escape(session),
+                        number.to_string(),
+                        time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                        line.food.id.to_string(),
+                        escape(&line.food.name),
+                        line.n.to_string(),
+                        line.price.to_string(),
+                        escape(order.payment_method.as_deref().unwrap_or("")),
+                        order
+                            .tendered
+                            .filter(|_| first)
+                            .map(|yen| yen.to_string())
+                            .unwrap_or_default(),
+                        order
+                            .change
+                            .filter(|_| first)
+                            .map(|yen| yen.to_string())
+                            .unwrap_or_default(),
+                        // 無効にした注文は 1 と理由を入れる
+                        (order.is_void() as u8).to_string(),
+                        escape(order.void.as_ref().map_or("", |void| void.reason.as_str())),
+                        escape(order.staff.as_deref().unwrap_or("")),
+                    ];
+                    csv += &row.join(",");
+                    csv.push('\n');
+                }
+            }
+            Order::CashUp(_) => {}
+        }
+    }
+
+    csv
+}
+
+/// 味ごとの個数（`sold_food_count`）を CSV にする
+pub fn summary_csv<'a>(counts: impl Iterator<Item = (&'a str, usize)>) -> String {
+    let mut csv = String::from("item,count\n");
+    for (name, count) in counts {
+        csv += &format!("{},{}\n", escape(name), count);
+    }
+
+    csv
+}
+
+/// CSV の1項目にする（カンマや引用符を含むときだけ引用符で囲む）
+pub fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// 読み込んだ記録を今の記録に足す
+/// 名前と開始時刻が同じセッションは1つにまとめ、同じ時刻の注文・レジ締めは足さない
+/// （セッションが違っても、同じ時刻の注文は同じ注文とみなす）
+/// 今のセッションは最後のままにする
+/// メニューの番号は端末やフォルダごとに振るので、読み込んだ行は名前で `menu` の番号に付け直す
+/// 注文のセッション番号は、このあと `normalize_sessions` で並びから付け直す
+pub fn merge(history: &mut Vec<Order>, mut imported: Vec<Order>, menu: &Menu) {
+    // 以前の記録は、今の記録と同じようにセッションに入れてから比べる
+    crate::session::normalize_sessions(&mut imported);
+    remap_ids(&mut imported, menu);
+
+    let mut merged = blocks(std::mem::take(history));
+    let mut current = merged.pop();
+
+    let mut next_id = merged
+        .iter()
+        .chain(current.iter())
+        .map(|(session, _)| session.id)
+        .max()
+        .unwrap_or(0)
+        + 1;
+
+    for (mut session, orders) in blocks(imported) {
+        let had_orders = !orders.is_empty();
+        let orders: Vec<Order> = orders
+            .into_iter()
+            .filter(|order| {
+                !merged
+                    .iter()
+                    .chain(current.iter())
+                    .any(|(_, existing)| existing.iter().any(|e| is_same(e, order)))
+            })
+            .collect();
+
+        let same = merged
+            .iter_mut()
+            .chain(current.iter_mut())
+            .find(|(s, _)| s.name == session.name && s.start == session.start);
+
+        match same {
+            Some((_, existing)) => {
+                existing.extend(orders);
+                existing.sort_by_key(|order| order.time());
+            }
+            // 注文が全部足してあるセッションは足さない
+            None if had_orders && orders.is_empty() => {}
+            None => {
+                session.id = next_id;
+                next_id += 1;
+                merged.push((session, orders));
+            }
+        }
+    }
+
+    merged.sort_by_key(|(session, _)| session.start);
+    merged.extend(current);
+
+    for (session, orders) in merged {
+        history.push(Order::Session(session));
+        history.extend(orders);
+    }
+}
+
+// 行のメニューの番号を、同じ名前の `menu` の番号にする（無ければ未割り当て）
+fn remap_ids(history: &mut [Order], menu: &Menu) {
+    let remap = |line: &mut OrderLine| {
+        line.food.id = menu.find_by_name(&line.food.name).map_or(0, |item| item.id);
+    };
+
+    for order in history {
+        if let Order::Food(order) = order {
+            order.lines.iter_mut().for_each(remap);
+            for amendment in &mut order.amendments {
+                amendment.before.iter_mut().for_each(remap);
+                amendment.after.iter_mut().for_each(remap);
+            }
+        }
+    }
+}
+
+// セッションごとに、その中の注文とレジ締めをまとめる
+// セッションより前の記録は、名前の無いセッションに入れる
+fn blocks(history: Vec<Order>) -> Vec<(Session, Vec<Order>)> {
+    let mut blocks: Vec<(Session, Vec<Order>)> = vec![];
+
+    for order in history {
+        match order {
+            Order::Session(session) => blocks.push((session, vec![])),
+            order => {
+                if blocks.is_empty() {
+                    let session = Session {
+                        id: 0,
+                        name: String::new(),
+                        start: None,
+                        float: 0,
+                    };
+                    blocks.push((session, vec![]));
+                }
+                if let Some((_, orders)) = blocks.last_mut() {
+                    orders.push(order);
+                }
+            }
+        }
+    }
+
+    blocks
+}
+
+fn is_same(a: &Order, b: &Order) -> bool {
+    match (a, b) {
+        (Order::Food(a), Order::Food(b)) => a.time == b.time,
+        (Order::CashUp(a), Order::CashUp(b)) => a.time == b.time,
+        _ => false,
+    }
+}
+
+/// ファイルに保存する
+/// ネイティブ版は保存先を選び、web 版はダウンロードする
+pub fn save_as(name: &str, mime: &str, text: &str) -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = mime;
+        if let Some(path) = rfd::FileDialog::new().set_file_name(name).save_file() {
+            crate::file::write_atomic(&path, text.as_bytes())?;
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        use eframe::wasm_bindgen::JsCast as _;
+
+        let js_error = |e: eframe::wasm_bindgen::JsValue| format!("{:?}", e);
+
+        let parts = js_sys::Array::of1(&text.into());
+        let options = web_sys::BlobPropertyBag::new();
+        options.set_type(mime);
+        let blob =
+            web_sys::Blob::new_with_str_sequence_and_options(&parts, &options).map_err(js_error)?;
+        let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(js_error)?;
+
+        let document = web_sys::window()
+            .and_then(|window| window.document())
+            .ok_or("No document")?;
+        let link = document
+            .create_element("a")
+            .map_err(js_error)?
+            .dyn_into::<web_sys::HtmlAnchorElement>()
+            .map_err(|_| "Not an anchor element")?;
+        link.set_href(&url);
+        link.set_download(name);
+        link.click();
+
+        web_sys::Url::revoke_object_url(&url).map_err(js_error)?;
+    }
+
+    Ok(())
+}
+
+/// CSV を BOM 付きの UTF-8 で保存する
+pub fn save_csv(name: &str, csv: &str) -> Result<(), Box<dyn std::error::Error>> {
+    save_as(name, "text/csv;charset=utf-8", &with_bom(csv))
+}
+
+fn with_bom(csv: &str) -> String {
+    format!("{}{}", BOM, csv)
+}
+
+/// 読み込む JSON ファイルを選んで、中身を `inbox` に入れる
+pub fn pick_json(inbox: &Inbox, ctx: &egui::Context) {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = ctx;
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .pick_file()
+        else {
+            return;
+        };
+        match std::fs::read(&path) {
+            Ok(bytes) => {
+                if let Ok(mut slot) = inbox.lock() {
+                    *slot = Some(bytes);
+                }
+            }
+            Err(e) => eprintln!("Failed to load file: {}", e),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        let dialog = rfd::AsyncFileDialog::new().add_filter("JSON", &["json"]);
+        let inbox = inbox.clone();
+        let ctx = ctx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Some(file) = dialog.pick_file().await {
+                let bytes = file.read().await;
+                if let Ok(mut slot) = inbox.lock() {
+                    *slot = Some(bytes);
+                }
+                ctx.request_repaint();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order::SoldFood;
+    use crate::test_util::food;
+
+    // プレーン3玉とチーズ2玉を、現金1000円で払った注文
+    fn two_lines(time: &str) -> Order {
+        let Order::Food(mut order) = food(time) else {
+            unreachable!()
+        };
+        order.lines.push(OrderLine {
+            food: SoldFood {
+                id: 2,
+                name: "チーズ".to_owned(),
+                time: order.time,
+            },
+            n: 2,
+            price: 250,
+        });
+        order.payment_method = Some("現金".to_owned());
+        order.tendered = Some(1000);
+        order.change = Some(450);
+        Order::Food(order)
+    }
+
+    fn food_count(history: &[Order]) -> usize {
+        history
+            .iter()
+            .filter(|order| matches!(order, Order::Food(_)))
+            .count()
+    }
+
+    #[test]
+    fn reimporting_export_adds_nothing() {
+        // セッションの無い以前の記録を読み込んだところ
+        let mut history = vec![food("2024-11-02T01:00:00Z"), food("2024-11-02T02:00:00Z")];
+        crate::session::normalize_sessions(&mut history);
+
+        let exported = crate::schema::to_string_pretty(&history).unwrap();
+        let imported: Vec<Order> = crate::schema::from_slice(exported.as_bytes()).unwrap();
+        merge(&mut history, imported, &Menu::default());
+        crate::session::normalize_sessions(&mut history);
+
+        assert_eq!(food_count(&history), 2);
+        assert_eq!(crate::session::sessions(&history).len(), 1);
+    }
+
+    #[test]
+    fn importing_legacy_history_again_adds_nothing() {
+        let legacy = vec![food("2024-11-02T01:00:00Z"), food("2024-11-02T02:00:00Z")];
+        let mut history = legacy.clone();
+        crate::session::normalize_sessions(&mut history);
+        // 読み込んだあとに新しいセッションを始めた
+        history.push(Order::Session(Session {
+            id: 2,
+            name: "2日目".to_owned(),
+            start: Some("2024-11-03T01:00:00Z".parse().unwrap()),
+            float: 0,
+        }));
+        history.push(food("2024-11-03T02:00:00Z"));
+
+        merge(&mut history, legacy, &Menu::default());
+        crate::session::normalize_sessions(&mut history);
+
+        assert_eq!(food_count(&history), 3);
+        assert_eq!(crate::session::sessions(&history).len(), 2);
+    }
+
+    #[test]
+    fn remaps_imported_items_by_name() {
+        let mut history = vec![food("2024-11-02T01:00:00Z")];
+        crate::session::normalize_sessions(&mut history);
+        // ほかの端末では、プレーンが2番でチーズが1番だった
+        let Order::Food(mut imported) = two_lines("2024-11-02T02:00:00Z") else {
+            unreachable!()
+        };
+        imported.lines[0].food.id = 2;
+        imported.lines[1].food.id = 1;
+        let mut menu = Menu::default();
+        menu.items.retain(|item| item.name != "チョコ");
+        menu.items[0].id = 7;
+
+        merge(&mut history, vec![Order::Food(imported)], &menu);
+
+        let Some(Order::Food(merged)) = history.last() else {
+            unreachable!()
+        };
+        let ids: Vec<(&str, u32)> = merged
+            .lines
+            .iter()
+            .map(|line| (line.food.name.as_str(), line.food.id))
+            .collect();
+        // チーズはこの端末のメニューに無いので、番号を付けない
+        assert_eq!(ids, [("プレーン", 7), ("チーズ", 0)]);
+    }
+
+    #[test]
+    fn puts_tendered_and_change_on_first_line_only() {
+        let csv = to_csv(&[two_lines("2024-11-02T01:00:00Z")]);
+        let rows: Vec<Vec<&str>> = csv.lines().map(|row| row.split(',').collect()).collect();
+
+        assert_eq!(rows.len(), 3);
+        // session,order,time,item_id,item,count,price,payment_method,tendered,change,…
+        assert_eq!(rows[1][1], "1");
+        assert_eq!(rows[2][1], "1");
+        assert_eq!(&rows[1][8..10], ["1000", "450"]);
+        assert_eq!(&rows[2][8..10], ["", ""]);
+        assert_eq!(rows[2][7], "現金");
+    }
+
+    #[test]
+    fn marks_every_line_of_void_order() {
+        let Order::Food(mut order) = two_lines("2024-11-02T01:00:00Z") else {
+            unreachable!()
+        };
+        order.void = Some(crate::void::Void {
+            time: "2024-11-02T01:05:00Z".parse().unwrap(),
+            reason: "打ち間違い".to_owned(),
+        });
+        let csv = to_csv(&[Order::Food(order), food("2024-11-02T02:00:00Z")]);
+        let rows: Vec<Vec<&str>> = csv.lines().map(|row| row.split(',').collect()).collect();
+
+        assert_eq!(rows.len(), 4);
+        for row in &rows[1..3] {
+            assert_eq!(&row[10..12], ["1", "打ち間違い"]);
+        }
+        assert_eq!(&rows[3][10..12], ["0", ""]);
+    }
+
+    #[test]
+    fn starts_csv_with_bom() {
+        let csv = with_bom(&summary_csv([("プレーン", 3)].into_iter()));
+
+        assert!(csv.as_bytes().starts_with(&[0xef, 0xbb, 0xbf]));
+        assert_eq!(csv.trim_start_matches(BOM), "item,count\nプレーン,3\n");
+    }
+}
@@ -1,4 +1,37 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
+mod amend;
 mod app;
+mod audit;
+mod cashup;
+mod chart;
+mod check;
+mod compare;
+#[cfg(not(target_arch = "wasm32"))]
+mod data_dir;
+mod event_log;
+mod file;
+mod ledger;
+mod menu;
+mod order;
+mod pin;
+mod register;
+mod report;
+mod schema;
+mod session;
+mod settings;
+mod staff;
+#[cfg(test)]
+mod test_util;
+mod undo;
+mod void;
+pub use amend::Amendment;
 pub use app::TemplateApp;
+pub use cashup::{CashUp, SessionReport};
+pub use menu::{Menu, MenuItem, Pack};
+pub use order::{FoodOrder, Order, OrderLine, SoldFood};
+pub use pin::PinHash;
+pub use session::Session;
+pub use settings::{PaymentMethod, Settings, StaffMember};
+pub use staff::{Shift, StaffTotal};
+pub use void::Void;
@@ -0,0 +1,299 @@
+/// まとめ買いの値段（例：3個で300円）
+#[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq)]
+pub struct Pack {
+    pub count: usize,
+    pub price: u32,
+}
+
+/// メニューの1品目
+#[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq)]
+pub struct MenuItem {
+    // 名前を変えても売上の記録と紐付けるための番号（0は未割り当て）
+    #[serde(default)]
+    pub id: u32,
+    pub name: String,
+    #[serde(default = "MenuItem::default_color")]
+    pub color: [u8; 3],
+    // 販売終了したメニューはボタンを出さない
+    #[serde(default)]
+    pub retired: bool,
+    // 1個あたりの値段（円）
+    #[serde(default)]
+    pub price: u32,
+    #[serde(default)]
+    pub packs: Vec<Pack>,
+}
+
+impl MenuItem {
+    pub fn new(id: u32, name: impl Into<String>, color: [u8; 3]) -> Self {
+        Self {
+            id,
+            name: name.into(),
+            color,
+            retired: false,
+            price: 0,
+            packs: vec![],
+        }
+    }
+
+    fn with_price(mut self, price: u32, packs: &[(usize, u32)]) -> Self {
+        self.price = price;
+        self.packs = packs
+            .iter()
+            .map(|&(count, price)| Pack { count, price })
+            .collect();
+        self
+    }
+
+    /// n個注文したときの値段
+    /// まとめ買いと単品を組み合わせて一番安くなる値段にする
+    /// もっと多く買うほうが安ければ、その値段にする（多く買ったほうが得にならないように）
+    pub fn price_for(&self, n: usize) -> u32 {
+        let largest = self.packs.iter().map(|pack| pack.count).max().unwrap_or(0);
+        let mut best = vec![0u32; n + largest + 1];
+        for i in 1..best.len() {
+            best[i] = best[i - 1] + self.price;
+            for pack in &self.packs {
+                if pack.count > 0 && pack.count <= i {
+                    best[i] = best[i].min(best[i - pack.count] + pack.price);
+                }
+            }
+        }
+        best[n..].iter().copied().min().unwrap_or(0)
+    }
+
+    fn default_color() -> [u8; 3] {
+        [200, 200, 200]
+    }
+
+    pub fn color32(&self) -> egui::Color32 {
+        let [r, g, b] = self.color;
+        egui::Color32::from_rgb(r, g, b)
+    }
+}
+
+/// 販売するメニューの一覧
+/// データファイルと同じ場所の `menu.json` から読み込む
+#[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq)]
+pub struct Menu {
+    pub items: Vec<MenuItem>,
+}
+
+impl Default for Menu {
+    fn default() -> Self {
+        // プレーン、チョコ、いちご、はちみつ、シナモン
+        Self {
+            items: vec![
+                MenuItem::new(1, "プレーン", [240, 220, 170])
+                    .with_price(100, &[(3, 300), (5, 450)]),
+                MenuItem::new(2, "チョコ", [140, 90, 60]).with_price(100, &[(3, 300), (5, 450)]),
+                MenuItem::new(3, "いちご", [240, 150, 170]).with_price(100, &[(3, 300), (5, 450)]),
+                MenuItem::new(4, "はちみつ", [240, 190, 60]).with_price(100, &[(3, 300), (5, 450)]),
+                MenuItem::new(5, "シナモン", [190, 130, 80]).with_price(100, &[(3, 300), (5, 450)]),
+            ],
+        }
+    }
+}
+
+impl crate::schema::Versioned for Menu {
+    const MIGRATIONS: &'static [crate::schema::Migration] = &[crate::schema::unchanged];
+}
+
+impl Menu {
+    pub fn get(&self, id: u32) -> Option<&MenuItem> {
+        self.items.iter().find(|item| item.id == id)
+    }
+
+    pub fn find_by_name(&self, name: &str) -> Option<&MenuItem> {
+        self.items.iter().find(|item| item.name == name)
+    }
+
+    /// 販売中のメニュー
+    pub fn active_items(&self) -> impl Iterator<Item = &MenuItem> {
+        self.items.iter().filter(|item| !item.retired)
+    }
+
+    fn next_id(&self) -> u32 {
+        self.items.iter().map(|item| item.id).max().unwrap_or(0) + 1
+    }
+
+    pub fn push(&mut self, name: impl Into<String>) {
+        let id = self.next_id();
+        self.items
+            .push(MenuItem::new(id, name, MenuItem::default_color()));
+    }
+
+    /// 変更の説明（操作の記録用）
+    pub fn changes(&self, after: &Menu) -> String {
+        let mut changes = vec![];
+        for item in &after.items {
+            match self.get(item.id) {
+                None => changes.push(format!("{}を追加", item.name)),
+                Some(old) if old == item => {}
+                Some(old) => {
+                    let mut what = vec![];
+                    if old.name != item.name {
+                        what.push(format!("名前 {}→{}", old.name, item.name));
+                    }
+                    if old.price != item.price || old.packs != item.packs {
+                        what.push(format!("値段 ¥{}→¥{}", old.price, item.price));
+                    }
+                    if old.retired != item.retired {
+                        what.push(
+                            if item.retired {
+                                "販売終了"
+                            } else {
+                                "販売再開"
+                            }
+                            .to_owned(),
+                        );
+                    }
+                    if old.color != item.color {
+                        what.push("色".to_owned());
+                    }
+                    if !what.is_empty() {
+                        changes.push(format!("{}：{}", item.name, what.join(" ")));
+                    }
+                }
+            }
+        }
+        for item in &self.items {
+            if after.get(item.id).is_none() {
+                changes.push(format!("{}を削除", item.name));
+            }
+        }
+        if changes.is_empty() {
+            changes.push("並び順".to_owned());
+        }
+
+        changes.join("、")
+    }
+
+    // 古い `menu.json` には番号が無いので、読み込んだときに振る
+    fn assign_missing_ids(&mut self) {
+        for i in 0..self.items.len() {
+            if self.items[i].id == 0 {
+                self.items[i].id = self.next_id();
+            }
+        }
+    }
+
+    /// 前のファイルを `backups/` に残してから置き換える
+    pub fn save_to_file(&self, path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+        crate::file::save_json(path, self)
+    }
+
+    /// ファイルが無ければ既定のメニューを書き出して、それを使う
+    pub fn load_from_file(path: &std::path::Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut menu: Self = crate::file::load_json(path)?;
+        menu.assign_missing_ids();
+
+        Ok(menu)
+    }
+
+    /// メニュー編集ウィンドウの中身
+    /// 追加・名前変更・並べ替え・色変更・販売終了ができる
+    pub fn editor_ui(&mut self, ui: &mut egui::Ui, new_name: &mut String) {
+        let len = self.items.len();
+        let mut swap = None;
+
+        egui::Grid::new("menu_editor_grid")
+            .num_columns(6)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("名前");
+                ui.label("色");
+                ui.label("単価");
+                ui.label("まとめ買い");
+                ui.end_row();
+
+                for (i, item) in self.items.iter_mut().enumerate() {
+                    ui.text_edit_singleline(&mut item.name);
+                    ui.color_edit_button_srgb(&mut item.color);
+                    ui.add(egui::DragValue::new(&mut item.price).prefix("¥"));
+                    ui.horizontal(|ui| {
+                        let mut remove = None;
+                        for (j, pack) in item.packs.iter_mut().enumerate() {
+                            ui.add(
+                                egui::DragValue::new(&mut pack.count)
+                                    .range(1..=99)
+                                    .suffix("個"),
+                            );
+                            ui.add(egui::DragValue::new(&mut pack.price).prefix("¥"));
+                            if ui.small_button("×").clicked() {
+                                remove = Some(j);
+                            }
+                        }
+                        if let Some(j) = remove {
+                            item.packs.remove(j);
+                        }
+                        if ui.small_button("+").clicked() {
+                            item.packs.push(Pack {
+                                count: 3,
+                                price: item.price.saturating_mul(3),
+                            });
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(i > 0, egui::Button::new("↑")).clicked() {
+                            swap = Some((i - 1, i));
+                        }
+                        if ui
+                            .add_enabled(i + 1 < len, egui::Button::new("↓"))
+                            .clicked()
+                        {
+                            swap = Some((i, i + 1));
+                        }
+                    });
+                    ui.checkbox(&mut item.retired, "販売終了");
+                    ui.end_row();
+                }
+            });
+
+        if let Some((a, b)) = swap {
+            self.items.swap(a, b);
+        }
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(new_name);
+            if ui
+                .add_enabled(!new_name.trim().is_empty(), egui::Button::new("追加"))
+                .clicked()
+            {
+                self.push(new_name.trim());
+                new_name.clear();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item() -> MenuItem {
+        MenuItem::new(1, "プレーン", [240, 220, 170]).with_price(100, &[(3, 250), (5, 320)])
+    }
+
+    #[test]
+    fn uses_pack_price_for_exact_pack_size() {
+        assert_eq!(item().price_for(3), 250);
+        assert_eq!(item().price_for(5), 320);
+    }
+
+    #[test]
+    fn adds_single_items_to_pack() {
+        assert_eq!(item().price_for(1), 100);
+        assert_eq!(item().price_for(7), 320 + 200);
+        assert_eq!(item().price_for(8), 320 + 250);
+    }
+
+    #[test]
+    fn never_charges_more_than_bigger_pack() {
+        // 4個をちょうど買うと 3個入り + 1個で350円だが、5個入りのほうが安い
+        assert_eq!(item().price_for(4), 320);
+        assert_eq!(item().price_for(0), 0);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::amend::Amendment;
+use crate::cashup::CashUp;
+use crate::session::Session;
+use crate::void::Void;
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct SoldFood {
+    // メニューの番号（0は古い記録で未割り当て）
+    #[serde(default)]
+    pub id: u32,
+    pub name: String,
+    pub time: chrono::DateTime<chrono::Utc>,
+}
+
+/// 注文の1行（味と個数）
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct OrderLine {
+    pub food: SoldFood,
+    pub n: usize,
+    // この行で実際に受け取った値段
+    pub price: u32,
+}
+
+/// 1回の注文（複数の味をまとめて1件）
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct FoodOrder {
+    pub time: chrono::DateTime<chrono::Utc>,
+    pub lines: Vec<OrderLine>,
+    // 現金で受け取った金額とお釣り（会計していなければ None）
+    #[serde(default)]
+    pub tendered: Option<u32>,
+    #[serde(default)]
+    pub change: Option<u32>,
+    // 支払い方法（設定の名前。古い記録には無い）
+    #[serde(default)]
+    pub payment_method: Option<String>,
+    // この注文が入っているセッションの番号
+    #[serde(default)]
+    pub session: u32,
+    // 無効にした理由（無効でなければ None）
+    #[serde(default)]
+    pub void: Option<Void>,
+    // 修正の記録（古い順）。`lines` は最後の修正のあとの中身
+    #[serde(default)]
+    pub amendments: Vec<Amendment>,
+    // 注文を受けた担当（担当を選んでいなかった注文や古い記録は None）
+    #[serde(default)]
+    pub staff: Option<String>,
+}
+
+impl FoodOrder {
+    pub fn new(time: chrono::DateTime<chrono::Utc>, lines: Vec<OrderLine>) -> Self {
+        Self {
+            time,
+            lines,
+            tendered: None,
+            change: None,
+            payment_method: None,
+            session: 0,
+            void: None,
+            amendments: vec![],
+            staff: None,
+        }
+    }
+
+    /// 無効にした注文は個数や売上に数えない
+    pub fn is_void(&self) -> bool {
+        self.void.is_some()
+    }
+
+    /// 注文の玉数
+    pub fn count(&self) -> usize {
+        self.lines.iter().map(|line| line.n).sum()
+    }
+
+    /// 注文の合計金額
+    pub fn total(&self) -> u32 {
+        self.lines.iter().map(|line| line.price).sum()
+    }
+
+    /// 会計したときの合計金額（修正する前の金額）
+    pub fn charged_total(&self) -> u32 {
+        match self.amendments.first() {
+            Some(amendment) => amendment.before.iter().map(|line| line.price).sum(),
+            None => self.total(),
+        }
+    }
+
+    /// 1玉ずつの記録に展開する
+    pub fn sold_food(&self) -> impl Iterator<Item = SoldFood> + '_ {
+        self.lines
+            .iter()
+            .flat_map(|line| std::iter::repeat(line.food.clone()).take(line.n))
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(from = "OrderRepr")]
+pub enum Order {
+    Food(FoodOrder),
+    // レジ締め（このあとに次のセッションが続く）
+    CashUp(CashUp),
+    // ここから新しいセッション
+    Session(Session),
+}
+
+impl Order {
+    /// 注文・レジ締めの時刻、セッションの開始時刻（分からなければ None）
+    pub fn time(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        match self {
+            Order::Food(order) => Some(order.time),
+            Order::CashUp(cash_up) => Some(cash_up.time),
+            Order::Session(session) => session.start,
+        }
+    }
+}
+
+// セッションが無かった頃の区切りも読めるようにする
+#[derive(serde::Deserialize)]
+enum OrderRepr {
+    Food(FoodOrder),
+    CashUp(CashUp),
+    Session(Session),
+    // セッションが無かった頃の区切り
+    Reset,
+}
+
+impl From<OrderRepr> for Order {
+    fn from(repr: OrderRepr) -> Self {
+        match repr {
+            OrderRepr::Food(order) => Order::Food(order),
+            OrderRepr::CashUp(cash_up) => Order::CashUp(cash_up),
+            OrderRepr::Session(session) => Order::Session(session),
+            // 番号と時刻は読み込んだあとに振る
+            OrderRepr::Reset => Order::Session(Session {
+                id: 0,
+                name: String::new(),
+                start: None,
+                float: 0,
+            }),
+        }
+    }
+}
+
+impl crate::schema::Versioned for Vec<Order> {
+    const MIGRATIONS: &'static [crate::schema::Migration] = &[lines_from_v0];
+}
+
+// 形式 0 → 1
+// 1回の注文が1つの味だけだった頃の `[商品, 個数, 値段]`（値段が無かった頃は `[商品, 個数]`）を、
+// 行のある注文にする
+fn lines_from_v0(
+    mut history: serde_json::Value,
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    let orders = history.as_array_mut().ok_or("History is not a list")?;
+    for order in orders {
+        let Some(food) = order.get_mut("Food") else {
+            continue;
+        };
+        let lines = match food.as_array().map(Vec::as_slice) {
+            Some([item, n, rest @ ..]) => serde_json::json!({
+                "time": item["time"],
+                "lines": [{
+                    "food": item,
+                    "n": n,
+                    "price": rest.first().cloned().unwrap_or(0.into()),
+                }],
+            }),
+            _ => continue,
+        };
+        *food = lines;
+    }
+
+    Ok(history)
+}
@@ -0,0 +1,52 @@
+//! 暗証番号の確かめ方
+//! 暗証番号は担当の取り違えやいたずらを防ぐためのもので、数字が数桁しかないので、
+//! ファイルを読める人に総当たりされれば分かってしまう
+//! 設定のファイルにそのまま見えないように、塩（人ごとに違う文字列）を付けた SHA-256 だけを残す
+
+use sha2::{Digest as _, Sha256};
+
+/// 暗証番号を確かめるための値
+#[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq)]
+pub struct PinHash {
+    salt: String,
+    hash: String,
+}
+
+impl PinHash {
+    pub fn new(pin: &str) -> Self {
+        // 秘密にする値ではないので、作った時刻から作る
+        let time = chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        let salt = hex(&Sha256::digest(time.to_string())[..16]);
+        let hash = hash(&salt, pin);
+
+        Self { salt, hash }
+    }
+
+    /// 入れた暗証番号が合っているか
+    pub fn matches(&self, pin: &str) -> bool {
+        hash(&self.salt, pin) == self.hash
+    }
+}
+
+fn hash(salt: &str, pin: &str) -> String {
+    hex(&Sha256::digest(format!("{}:{}", salt, pin)))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_pin_without_storing_it() {
+        let pin = PinHash::new("1234");
+
+        assert!(pin.matches("1234"));
+        assert!(!pin.matches("4321"));
+        // 同じ暗証番号でも塩が違えば別の値になる
+        assert!(PinHash::new("1234") != pin);
+    }
+}
@@ -0,0 +1,107 @@
+/// 注文を確定したあとに出す会計パネル
+/// 預かり金額を入れてお釣りを出す
+pub struct Register {
+    // 会計する注文の `history` 上の位置
+    pub order: usize,
+    pub total: u32,
+    pub tendered: u32,
+}
+
+pub enum RegisterAction {
+    None,
+    // 預かり金額とお釣りを注文に記録する
+    Pay { tendered: u32, change: u32 },
+    Skip,
+}
+
+impl Register {
+    pub fn new(order: usize, total: u32) -> Self {
+        Self {
+            order,
+            total,
+            tendered: 0,
+        }
+    }
+
+    pub fn change(&self) -> Option<u32> {
+        self.tendered.checked_sub(self.total)
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, text_size: f32) -> RegisterAction {
+        let mut action = RegisterAction::None;
+        let key_size = egui::vec2(text_size * 3.0, text_size * 2.0);
+        let key = |ui: &mut egui::Ui, text: &str| {
+            ui.add(egui::Button::new(egui::RichText::new(text).size(text_size)).min_size(key_size))
+                .clicked()
+        };
+
+        ui.label(egui::RichText::new(format!("合計 ¥{}", self.total)).size(text_size * 1.5));
+        ui.label(egui::RichText::new(format!("預かり ¥{}", self.tendered)).size(text_size * 1.5));
+        match self.change() {
+            Some(change) => {
+                ui.label(egui::RichText::new(format!("お釣り ¥{}", change)).size(text_size * 2.0))
+            }
+            None => ui.label(
+                egui::RichText::new(format!("不足 ¥{}", self.total - self.tendered))
+                    .size(text_size * 2.0)
+                    .color(egui::Color32::RED),
+            ),
+        };
+
+        ui.separator();
+
+        // よく使う金額
+        ui.horizontal(|ui| {
+            for amount in [500, 1000, 5000, 10000] {
+                if key(ui, &format!("¥{}", amount)) {
+                    self.tendered = amount;
+                }
+            }
+        });
+
+        // テンキー
+        egui::Grid::new("register_keypad").show(ui, |ui| {
+            for row in [
+                ["7", "8", "9"],
+                ["4", "5", "6"],
+                ["1", "2", "3"],
+                ["0", "00", "C"],
+            ] {
+                for k in row {
+                    if key(ui, k) {
+                        if k == "C" {
+                            self.tendered = 0;
+                        } else {
+                            let digits = format!("{}{}", self.tendered, k);
+                            self.tendered = digits.parse().unwrap_or(self.tendered);
+                        }
+                    }
+                }
+                ui.end_row();
+            }
+        });
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            let pay = ui.add_enabled(
+                self.change().is_some(),
+                egui::Button::new(egui::RichText::new("会計完了").size(text_size))
+                    .min_size(key_size),
+            );
+            if pay.clicked() {
+                if let Some(change) = self.change() {
+                    action = RegisterAction::Pay {
+                        tendered: self.tendered,
+                        change,
+                    };
+                }
+            }
+            if key(ui, "スキップ") {
+                action = RegisterAction::Skip;
+            }
+        });
+
+        action
+    }
+}
@@ -0,0 +1,248 @@
+//! 印刷用のセッションの報告書（1つの HTML ファイル）
+//! 文字化けしないように、アプリと同じ Noto Sans JP をファイルに埋め込む
+//! ネットにつながっていなくても開けて、ブラウザの印刷から PDF にもできる
+
+use base64::Engine as _;
+
+use crate::cashup::CashUp;
+use crate::chart::{Flavor, TimeSeries};
+use crate::session::Session;
+
+/// 報告書に載せる内容
+pub struct Printout<'a> {
+    pub session: &'a Session,
+    // 次のセッションが始まった時刻（今のセッションなら None）
+    pub end: Option<chrono::DateTime<chrono::Utc>>,
+    pub orders: usize,
+    pub sold: usize,
+    pub revenue: u32,
+    pub flavors: Vec<Flavor>,
+    pub revenue_by_method: Vec<(String, u32)>,
+    // 1時間ごとの玉数と注文数（長いセッションは区切りを長くして、24本までにする）
+    pub hourly: TimeSeries,
+    // 無効にした注文の時刻・中身・理由
+    pub voids: Vec<(chrono::DateTime<chrono::Utc>, String, String)>,
+    // セッションを締めたときの記録（まだ締めていなければ None）
+    pub cash_up: Option<&'a CashUp>,
+}
+
+fn local(time: chrono::DateTime<chrono::Utc>) -> String {
+    time.with_timezone(&chrono::Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+impl Printout<'_> {
+    /// HTML にする
+    pub fn to_html(&self) -> String {
+        let mut body = String::new();
+
+        body += &format!("<h1>{}</h1>\n", escape(&self.session.name));
+        body += &format!(
+            "<p>{} 〜 {}　（作成 {}）</p>\n",
+            self.session.start.map_or("不明".to_owned(), local),
+            self.end.map_or("営業中".to_owned(), local),
+            local(chrono::Utc::now()),
+        );
+
+        body += "<h2>合計</h2>\n<table>\n";
+        body += &row(&["注文数", &format!("{}件", self.orders)]);
+        body += &row(&["玉数", &format!("{}玉", self.sold)]);
+        body += &row(&["売上", &format!("¥{}", self.revenue)]);
+        body += "</table>\n";
+
+        body += "<h2>味ごと</h2>\n<table>\n";
+        for flavor in &self.flavors {
+            let [r, g, b] = flavor.color;
+            body += &format!(
+                "<tr><td><span class=\"swatch\" style=\"background: rgb({}, {}, {})\"></span>{}</td><td>{}玉</td></tr>\n",
+                r,
+                g,
+                b,
+                escape(&flavor.name),
+                flavor.count,
+            );
+        }
+        body += "</table>\n";
+
+        body += "<h2>支払い方法ごと</h2>\n<table>\n";
+        for (name, total) in &self.revenue_by_method {
+            body += &row(&[name, &format!("¥{}", total)]);
+        }
+        body += "</table>\n";
+
+        body += "<h2>時間ごと</h2>\n";
+        body += &hourly_svg(&self.hourly);
+
+        body += "<h2>無効にした注文</h2>\n";
+        if self.voids.is_empty() {
+            body += "<p>なし</p>\n";
+        } else {
+            body += "<table>\n";
+            for (time, summary, reason) in &self.voids {
+                body += &row(&[&local(*time), summary, reason]);
+            }
+            body += "</table>\n";
+        }
+
+        body += "<h2>レジ締め</h2>\n";
+        match self.cash_up {
+            Some(cash_up) => {
+                body += "<table>\n";
+                body += &row(&["釣り銭準備金", &format!("¥{}", cash_up.float)]);
+                body += &row(&["あるはずの金額", &format!("¥{}", cash_up.expected)]);
+                body += &row(&["数えた金額", &format!("¥{}", cash_up.counted_total())]);
+                body += &row(&["過不足", &format!("{:+}円", cash_up.difference())]);
+                body += "</table>\n";
+            }
+            None => body += "<p>まだ締めていません</p>\n",
+        }
+
+        format!(
+            "<!DOCTYPE html>
+<html lang=\"ja\">
+<head>
+<meta charset=\"utf-8\">
+<title>{}</title>
+<style>
+@font-face {{ font-family: \"Noto Sans JP\"; src: url(data:font/ttf;base64,{}); }}
+@page {{ size: A4; margin: 15mm; }}
+body {{ font-family: \"Noto Sans JP\", sans-serif; color: #000; }}
+table {{ border-collapse: collapse; margin-bottom: 1em; }}
+td {{ border: 1px solid #999; padding: 2px 8px; }}
+td:last-child {{ text-align: right; }}
+.swatch {{ display: inline-block; width: 1em; height: 1em; margin-right: 4px; vertical-align: middle; }}
+</style>
+</head>
+<body>
+{}</body>
+</html>
+",
+            escape(&self.session.name),
+            base64::engine::general_purpose::STANDARD.encode(crate::app::NOTO_SANS_JP),
+            body,
+        )
+    }
+}
+
+// 表の1行
+fn row(cells: &[&str]) -> String {
+    let cells: Vec<String> = cells
+        .iter()
+        .map(|cell| format!("<td>{}</td>", escape(cell)))
+        .collect();
+    format!("<tr>{}</tr>\n", cells.concat())
+}
+
+// 1時間ごとに、味ごとの玉数を積み上げた棒（上に注文数）
+fn hourly_svg(data: &TimeSeries) -> String {
+    const WIDTH: usize = 60;
+    const HEIGHT: f64 = 160.0;
+
+    if data.orders.is_empty() {
+        return "<p>注文がありません</p>\n".to_owned();
+    }
+
+    let len = data.orders.len();
+    let totals: Vec<usize> = (0..len)
+        .map(|i| data.series.iter().map(|series| series.counts[i]).sum())
+        .collect();
+    let max = totals.iter().copied().max().unwrap_or(0).max(1);
+    let scale = HEIGHT / max as f64;
+
+    // 棒が多くても紙の幅に収まるように縮める
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {} {}\" style=\"width: 100%; max-width: {}px\" font-size=\"12\">\n",
+        len * WIDTH,
+        HEIGHT + 40.0,
+        len * WIDTH,
+    );
+    for i in 0..len {
+        let x = i * WIDTH;
+        let mut top = HEIGHT + 20.0;
+        for series in &data.series {
+            let height = series.counts[i] as f64 * scale;
+            top -= height;
+            let [r, g, b] = series.color;
+            svg += &format!(
+                "<rect x=\"{}\" y=\"{:.1}\" width=\"{}\" height=\"{:.1}\" fill=\"rgb({}, {}, {})\"/>\n",
+                x + 8,
+                top,
+                WIDTH - 16,
+                height,
+                r,
+                g,
+                b,
+            );
+        }
+        svg += &format!(
+            "<text x=\"{}\" y=\"{:.1}\" text-anchor=\"middle\">{}玉 {}件</text>\n",
+            x + WIDTH / 2,
+            top - 4.0,
+            totals[i],
+            data.orders[i],
+        );
+        svg += &format!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n",
+            x + WIDTH / 2,
+            HEIGHT + 36.0,
+            data.label(i),
+        );
+    }
+    svg += "</svg>\n";
+
+    svg
+}
+
+// HTML に入れる文字をエスケープする
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shows_totals_voids_and_cash_up_difference() {
+        let session = Session {
+            id: 1,
+            name: "2024-11-02".to_owned(),
+            start: Some("2024-11-02T00:30:00Z".parse().unwrap()),
+            float: 1000,
+        };
+        let cash_up = CashUp {
+            time: "2024-11-02T09:00:00Z".parse().unwrap(),
+            float: 1000,
+            counted: vec![(1000, 1), (100, 3)],
+            expected: 1400,
+        };
+        let printout = Printout {
+            session: &session,
+            end: None,
+            orders: 2,
+            sold: 7,
+            revenue: 700,
+            flavors: vec![],
+            revenue_by_method: vec![("現金".to_owned(), 400)],
+            hourly: TimeSeries::new(std::iter::empty(), &crate::menu::Menu::default(), 60, 24),
+            voids: vec![(
+                "2024-11-02T01:00:00Z".parse().unwrap(),
+                "プレーン×3".to_owned(),
+                "打ち間違い<二重>".to_owned(),
+            )],
+            cash_up: Some(&cash_up),
+        };
+
+        let html = printout.to_html();
+
+        assert!(html.contains("<tr><td>注文数</td><td>2件</td></tr>"));
+        assert!(html.contains("<tr><td>玉数</td><td>7玉</td></tr>"));
+        assert!(html.contains("<tr><td>売上</td><td>¥700</td></tr>"));
+        assert!(html.contains("<td>プレーン×3</td><td>打ち間違い&lt;二重&gt;</td>"));
+        assert!(html.contains("<tr><td>過不足</td><td>-100円</td></tr>"));
+    }
+}
@@ -0,0 +1,264 @@
+//! 保存するデータの形式の番号と、古い形式からの変換
+//!
+//! ファイルには `{"version": 1, "data": ...}` の形で番号を付けて保存する
+//! 番号の無いファイルは、番号を付ける前の形式 0 として読む
+
+/// 形式 i のデータを形式 i + 1 にする変換
+pub type Migration = fn(serde_json::Value) -> Result<serde_json::Value, Box<dyn std::error::Error>>;
+
+/// 形式の番号を付けて保存するデータ
+/// 形式を変えたら `MIGRATIONS` の最後に変換を1つ足す（今の形式の番号は `MIGRATIONS.len()`）
+pub trait Versioned: serde::Serialize + serde::de::DeserializeOwned {
+    const MIGRATIONS: &'static [Migration];
+
+    fn version() -> u32 {
+        Self::MIGRATIONS.len() as u32
+    }
+}
+
+/// 新しいアプリで保存されたデータ
+/// 古いアプリでは読めないので、バックアップで置き換えたりしない
+#[derive(Debug)]
+pub struct UnsupportedVersion {
+    pub version: u32,
+    pub newest: u32,
+}
+
+impl std::fmt::Display for UnsupportedVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Unsupported data version {} (newest known is {})",
+            self.version, self.newest
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedVersion {}
+
+#[derive(serde::Serialize)]
+struct Envelope<'a, T> {
+    version: u32,
+    data: &'a T,
+}
+
+/// 番号を付けただけで、中身は前の形式と同じ
+pub fn unchanged(
+    value: serde_json::Value,
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    Ok(value)
+}
+
+/// 形式の番号を付けて、改行の無い JSON にする
+pub fn to_string<T: Versioned>(value: &T) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(serde_json::to_string(&Envelope {
+        version: T::version(),
+        data: value,
+    })?)
+}
+
+/// 形式の番号を付けて JSON にする
+pub fn to_string_pretty<T: Versioned>(value: &T) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(serde_json::to_string_pretty(&Envelope {
+        version: T::version(),
+        data: value,
+    })?)
+}
+
+/// 形式の番号を見て、今の形式に変換してから読む
+pub fn from_slice<T: Versioned>(bytes: &[u8]) -> Result<T, Box<dyn std::error::Error>> {
+    let value: serde_json::Value = serde_json::from_slice(bytes)?;
+
+    match value {
+        serde_json::Value::Object(mut object)
+            if object.len() == 2 && object.contains_key("data") =>
+        {
+            let version = object
+                .get("version")
+                .and_then(|version| version.as_u64())
+                .ok_or("Missing data version")?;
+            let data = object.remove("data").unwrap_or_default();
+            migrate(version as u32, data)
+        }
+        value => migrate(0, value),
+    }
+}
+
+/// 形式 `version` のデータを今の形式にして読む
+pub fn migrate<T: Versioned>(
+    version: u32,
+    mut data: serde_json::Value,
+) -> Result<T, Box<dyn std::error::Error>> {
+    check_version::<T>(version)?;
+
+    for migration in &T::MIGRATIONS[version as usize..] {
+        data = migration(data)?;
+    }
+
+    Ok(serde_json::from_value(data)?)
+}
+
+/// 今のアプリで読める形式か確かめる
+pub fn check_version<T: Versioned>(version: u32) -> Result<(), UnsupportedVersion> {
+    if version > T::version() {
+        return Err(UnsupportedVersion {
+            version,
+            newest: T::version(),
+        });
+    }
+
+    Ok(())
+}
+
+/// 1行ずつのファイル（`events.jsonl`）の先頭に置く形式の番号
+pub fn header<T: Versioned>() -> Result<String, Box<dyn std::error::Error>> {
+    Ok(serde_json::to_string(
+        &serde_json::json!({ "version": T::version() }),
+    )?)
+}
+
+/// 先頭の行が形式の番号なら、その番号を返す
+pub fn parse_header(line: &str) -> Option<u32> {
+    let value: serde_json::Value = serde_json::from_str(line).ok()?;
+    let object = value.as_object()?;
+    if object.len() != 1 {
+        return None;
+    }
+
+    object
+        .get("version")?
+        .as_u64()
+        .map(|version| version as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_log::Event;
+    use crate::order::Order;
+    use crate::{Menu, SessionReport, Settings};
+
+    // 形式に番号を付ける前（形式 0）のファイル
+    // 読めなくなったら、形式を変えたときの変換が足りていない
+
+    #[test]
+    fn loads_unversioned_history() {
+        // 最初の版の `history.json`（注文とリセットだけ）
+        let history: Vec<Order> =
+            from_slice(include_bytes!("../tests/golden/v0/history.json")).unwrap();
+
+        assert_eq!(history.len(), 5);
+        assert!(
+            matches!(&history[0], Order::Food(order) if order.count() == 2 && order.total() == 0)
+        );
+        assert!(matches!(&history[1], Order::Food(order) if order.lines[0].food.name == "チョコ"));
+        assert!(matches!(&history[2], Order::Session(session) if session.id == 0));
+        assert!(matches!(&history[3], Order::Food(order) if order.count() == 3));
+        assert!(matches!(&history[4], Order::Food(order) if order.count() == 1));
+    }
+
+    #[test]
+    fn loads_unversioned_later_history() {
+        // 形式に番号を付ける前に、値段・複数の味・レジ締め・セッションを足した記録
+        let history: Vec<Order> =
+            from_slice(include_bytes!("../tests/golden/v0/history_later.json")).unwrap();
+
+        assert_eq!(history.len(), 6);
+        assert!(
+            matches!(&history[0], Order::Food(order) if order.count() == 2 && order.total() == 0)
+        );
+        assert!(matches!(&history[1], Order::Food(order) if order.total() == 300));
+        assert!(matches!(&history[2], Order::Session(session) if session.id == 0));
+        assert!(
+            matches!(&history[3], Order::Food(order) if order.lines.len() == 2 && order.total() == 400)
+        );
+        assert!(matches!(&history[4], Order::CashUp(cash_up) if cash_up.counted_total() == 1400));
+        assert!(matches!(&history[5], Order::Session(session) if session.name == "2024-11-03"));
+    }
+
+    #[test]
+    fn loads_unversioned_menu() {
+        let menu: Menu = from_slice(include_bytes!("../tests/golden/v0/menu.json")).unwrap();
+
+        assert_eq!(menu.items.len(), 2);
+        assert_eq!(menu.items[0].name, "プレーン");
+        assert_eq!(menu.items[0].price_for(3), 300);
+        assert!(menu.items[1].retired);
+    }
+
+    #[test]
+    fn loads_unversioned_settings() {
+        let settings: Settings =
+            from_slice(include_bytes!("../tests/golden/v0/settings.json")).unwrap();
+
+        assert_eq!(settings.payment_methods.len(), 2);
+        assert!(settings
+            .payment_method("現金")
+            .is_some_and(|method| method.cash));
+    }
+
+    #[test]
+    fn loads_unversioned_report() {
+        let report: SessionReport =
+            from_slice(include_bytes!("../tests/golden/v0/report.json")).unwrap();
+
+        assert_eq!(report.session.name, "2024-11-02");
+        assert_eq!(report.revenue, 700);
+        assert_eq!(report.difference, 0);
+    }
+
+    #[test]
+    fn loads_unversioned_events() {
+        let text = include_str!("../tests/golden/v0/events.jsonl");
+        assert_eq!(parse_header(text.lines().next().unwrap()), None);
+
+        let mut history = vec![];
+        for line in text.lines() {
+            let event: Event = migrate(0, serde_json::from_str(line).unwrap()).unwrap();
+            event.apply(&mut history);
+        }
+
+        assert_eq!(history.len(), 2);
+        assert!(
+            matches!(&history[1], Order::Food(order) if order.tendered == Some(500) && order.change == Some(200))
+        );
+    }
+
+    #[test]
+    fn round_trips_current_version() {
+        let history: Vec<Order> =
+            from_slice(include_bytes!("../tests/golden/v0/history_later.json")).unwrap();
+        let text = to_string_pretty(&history).unwrap();
+        assert!(text.contains("\"version\": 1"));
+
+        let again: Vec<Order> = from_slice(text.as_bytes()).unwrap();
+        assert_eq!(again.len(), history.len());
+
+        // ブラウザの保存領域には改行なしで書く
+        let compact = to_string(&history).unwrap();
+        assert!(!compact.contains('\n'));
+        let again: Vec<Order> = from_slice(compact.as_bytes()).unwrap();
+        assert_eq!(again.len(), history.len());
+    }
+
+    #[test]
+    fn hashes_plain_staff_pins() {
+        let text = r#"{"version": 1, "data": {"staff": [
+            {"name": "佐藤", "pin": "1234"},
+            {"name": "鈴木", "pin": ""}
+        ]}}"#;
+        let settings: Settings = from_slice(text.as_bytes()).unwrap();
+
+        assert!(settings.staff[0]
+            .pin
+            .as_ref()
+            .is_some_and(|pin| pin.matches("1234")));
+        assert!(settings.staff[1].pin.is_none());
+    }
+
+    #[test]
+    fn rejects_newer_version() {
+        let text = r#"{"version": 99, "data": {"items": []}}"#;
+        assert!(from_slice::<Menu>(text.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use crate::order::Order;
+
+/// 営業日などの区切り
+/// `history` の中では、このあとの注文がこのセッションに入る
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct Session {
+    pub id: u32,
+    pub name: String,
+    // 開始時刻（以前の Reset から作ったばかりで分からなければ None）
+    #[serde(default, deserialize_with = "start_from_time_or_option")]
+    pub start: Option<chrono::DateTime<chrono::Utc>>,
+    // 釣り銭準備金
+    #[serde(default)]
+    pub float: u32,
+}
+
+// 以前の形式（web 版の RON）では、開始時刻を Option でなく時刻そのものとして書いていた
+fn start_from_time_or_option<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<chrono::DateTime<chrono::Utc>>, D::Error> {
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Start {
+        Time(chrono::DateTime<chrono::Utc>),
+        Option(Option<chrono::DateTime<chrono::Utc>>),
+    }
+
+    Ok(match serde::Deserialize::deserialize(deserializer)? {
+        Start::Time(time) => Some(time),
+        Start::Option(start) => start,
+    })
+}
+
+/// `history` の中の1つのセッションの範囲
+pub struct SessionRange<'a> {
+    pub session: &'a Session,
+    // セッションの記録を含む `history` 上の範囲
+    pub range: std::ops::Range<usize>,
+    // 次のセッションが始まった時刻（今のセッションなら None）
+    pub end: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// `history` をセッションごとに区切る
+pub fn sessions(history: &[Order]) -> Vec<SessionRange<'_>> {
+    let mut ranges: Vec<SessionRange<'_>> = vec![];
+
+    for (i, order) in history.iter().enumerate() {
+        if let Order::Session(session) = order {
+            if let Some(last) = ranges.last_mut() {
+                last.range.end = i;
+                last.end = session.start;
+            }
+            ranges.push(SessionRange {
+                session,
+                range: i..history.len(),
+                end: None,
+            });
+        }
+    }
+
+    ranges
+}
+
+/// 次に始めるセッションの番号
+pub fn next_id(history: &[Order]) -> u32 {
+    sessions(history)
+        .iter()
+        .map(|range| range.session.id)
+        .max()
+        .unwrap_or(0)
+        + 1
+}
+
+/// 読み込んだ履歴をセッションごとに整える
+/// 以前の Reset から作ったセッションに番号・名前・開始時刻を振り、
+/// 注文にセッションの番号を付ける
+pub fn normalize_sessions(history: &mut Vec<Order>) {
+    if !matches!(history.first(), Some(Order::Session(_))) {
+        history.insert(
+            0,
+            Order::Session(Session {
+                id: 0,
+                name: String::new(),
+                start: None,
+                float: 0,
+            }),
+        );
+    }
+
+    let mut id = next_id(history);
+    let mut current = 0;
+    for i in 0..history.len() {
+        // 開始時刻が分からなければ、セッションの最初の記録の時刻を使う
+        // 記録が無ければ、その前の記録（前のセッションの注文やレジ締め）の時刻を使う
+        // 以前の版は、分からない開始時刻の代わりに `MIN_UTC` を保存していた
+        let no_start = matches!(
+            &history[i],
+            Order::Session(session)
+                if session.start.map_or(true, |start| start == chrono::DateTime::<chrono::Utc>::MIN_UTC)
+        );
+        let near_time = no_start
+            .then(|| {
+                history[i + 1..]
+                    .iter()
+                    .take_while(|order| !matches!(order, Order::Session(_)))
+                    .find_map(Order::time)
+                    .or_else(|| history[..i].iter().rev().find_map(Order::time))
+            })
+            .flatten();
+
+        match &mut history[i] {
+            Order::Session(session) => {
+                if session.id == 0 {
+                    session.id = id;
+                    id += 1;
+                }
+                if session.name.is_empty() {
+                    session.name = format!("セッション{}", session.id);
+                }
+                if no_start {
+                    session.start = near_time;
+                }
+                current = session.id;
+            }
+            Order::Food(order) => order.session = current,
+            Order::CashUp(_) => {}
+        }
+    }
+}
+
+/// 新しいセッションを始めるときの入力
+pub struct NewSessionForm {
+    pub name: String,
+    pub float: u32,
+}
+
+impl Default for NewSessionForm {
+    fn default() -> Self {
+        Self {
+            name: chrono::Local::now().format("%Y-%m-%d").to_string(),
+            float: 0,
+        }
+    }
+}
+
+impl NewSessionForm {
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("new_session_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("名前");
+                ui.text_edit_singleline(&mut self.name);
+                ui.end_row();
+
+                ui.label("釣り銭準備金");
+                ui.add(egui::DragValue::new(&mut self.float).prefix("¥"));
+                ui.end_row();
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::food;
+
+    fn reset() -> Order {
+        Order::Session(Session {
+            id: 0,
+            name: String::new(),
+            start: None,
+            float: 0,
+        })
+    }
+
+    fn starts(history: &[Order]) -> Vec<Option<String>> {
+        sessions(history)
+            .iter()
+            .map(|range| range.session.start.map(|start| start.to_rfc3339()))
+            .collect()
+    }
+
+    #[test]
+    fn starts_sessions_at_their_first_or_previous_record() {
+        let mut history = vec![
+            food("2024-11-02T01:00:00Z"),
+            reset(),
+            // 注文の無いセッション
+            reset(),
+            food("2024-11-03T01:00:00Z"),
+        ];
+
+        normalize_sessions(&mut history);
+
+        assert_eq!(
+            starts(&history),
+            [
+                Some("2024-11-02T01:00:00+00:00".to_owned()),
+                Some("2024-11-02T01:00:00+00:00".to_owned()),
+                Some("2024-11-03T01:00:00+00:00".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_start_unknown_without_records() {
+        let mut history = vec![];
+
+        normalize_sessions(&mut history);
+
+        assert_eq!(starts(&history), [None]);
+    }
+}
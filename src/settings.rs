@@ -0,0 +1,107 @@
+use crate::pin::PinHash;
+
+/// 支払い方法
+#[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq)]
+pub struct PaymentMethod {
+    pub name: String,
+    // 現金ならお釣りの計算をする
+    #[serde(default)]
+    pub cash: bool,
+}
+
+impl PaymentMethod {
+    fn new(name: &str, cash: bool) -> Self {
+        Self {
+            name: name.to_owned(),
+            cash,
+        }
+    }
+}
+
+/// レジを担当する人
+#[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq)]
+pub struct StaffMember {
+    pub name: String,
+    // 交代するときに入れる暗証番号（無ければ None）
+    #[serde(default)]
+    pub pin: Option<PinHash>,
+}
+
+/// お店の設定
+/// データファイルと同じ場所の `settings.json` から読み込む
+#[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    pub payment_methods: Vec<PaymentMethod>,
+    // 担当を選ぶ一覧
+    pub staff: Vec<StaffMember>,
+    // 担当の一覧を変えるときに入れる暗証番号（まだ決めていなければ None）
+    pub admin_pin: Option<PinHash>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            payment_methods: vec![
+                PaymentMethod::new("現金", true),
+                PaymentMethod::new("QR決済", false),
+                PaymentMethod::new("交通系IC", false),
+                PaymentMethod::new("金券", false),
+            ],
+            staff: vec![],
+            admin_pin: None,
+        }
+    }
+}
+
+impl crate::schema::Versioned for Settings {
+    const MIGRATIONS: &'static [crate::schema::Migration] =
+        &[crate::schema::unchanged, hash_staff_pins];
+}
+
+// 形式 1 → 2
+// 担当の暗証番号をそのまま書いていたのを、塩を付けたハッシュにする（空なら無し）
+fn hash_staff_pins(
+    mut settings: serde_json::Value,
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    let Some(staff) = settings
+        .get_mut("staff")
+        .and_then(|staff| staff.as_array_mut())
+    else {
+        return Ok(settings);
+    };
+    for member in staff {
+        let Some(fields) = member.as_object_mut() else {
+            continue;
+        };
+        let pin = match fields.get("pin").and_then(|pin| pin.as_str()) {
+            Some("") | None => serde_json::Value::Null,
+            Some(pin) => serde_json::to_value(PinHash::new(pin))?,
+        };
+        fields.insert("pin".to_owned(), pin);
+    }
+
+    Ok(settings)
+}
+
+impl Settings {
+    pub fn payment_method(&self, name: &str) -> Option<&PaymentMethod> {
+        self.payment_methods
+            .iter()
+            .find(|method| method.name == name)
+    }
+
+    pub fn staff_member(&self, name: &str) -> Option<&StaffMember> {
+        self.staff.iter().find(|member| member.name == name)
+    }
+
+    /// 前のファイルを `backups/` に残してから置き換える
+    pub fn save_to_file(&self, path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+        crate::file::save_json(path, self)
+    }
+
+    /// ファイルが無ければ既定の設定を書き出して、それを使う
+    pub fn load_from_file(path: &std::path::Path) -> Result<Self, Box<dyn std::error::Error>> {
+        crate::file::load_json(path)
+    }
+}
@@ -0,0 +1,383 @@
+//! レジの担当の交代と、担当ごとの集計
+
+use crate::order::FoodOrder;
+use crate::pin::PinHash;
+use crate::settings::{Settings, StaffMember};
+
+// 担当を選んでいなかった注文をまとめる名前
+const NO_STAFF: &str = "未記録";
+
+/// 担当ごとの注文数・玉数・売上
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct StaffTotal {
+    pub name: String,
+    pub orders: usize,
+    pub sold: usize,
+    pub revenue: u32,
+}
+
+/// 同じ担当が続けて注文を受けた間（1回のシフト）
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct Shift {
+    pub name: String,
+    // 最初と最後の注文の時刻
+    pub start: chrono::DateTime<chrono::Utc>,
+    pub end: chrono::DateTime<chrono::Utc>,
+    pub orders: usize,
+    pub sold: usize,
+    pub revenue: u32,
+}
+
+fn name_of(order: &FoodOrder) -> &str {
+    order.staff.as_deref().unwrap_or(NO_STAFF)
+}
+
+/// 担当ごとに集計する（最初に注文を受けた順）
+pub fn totals<'a>(orders: impl Iterator<Item = &'a FoodOrder>) -> Vec<StaffTotal> {
+    let mut totals: Vec<StaffTotal> = vec![];
+
+    for order in orders {
+        let name = name_of(order);
+        let total = match totals.iter().position(|total| total.name == name) {
+            Some(i) => &mut totals[i],
+            None => {
+                totals.push(StaffTotal {
+                    name: name.to_owned(),
+                    orders: 0,
+                    sold: 0,
+                    revenue: 0,
+                });
+                totals.last_mut().expect("just pushed")
+            }
+        };
+        total.orders += 1;
+        total.sold += order.count();
+        total.revenue += order.total();
+    }
+
+    totals
+}
+
+/// 担当が替わるところで区切って、シフトごとに集計する（時刻順）
+pub fn shifts<'a>(orders: impl Iterator<Item = &'a FoodOrder>) -> Vec<Shift> {
+    let mut orders: Vec<&FoodOrder> = orders.collect();
+    orders.sort_by_key(|order| order.time);
+
+    let mut shifts: Vec<Shift> = vec![];
+    for order in orders {
+        match shifts.last_mut() {
+            Some(shift) if shift.name == name_of(order) => {
+                shift.end = order.time;
+                shift.orders += 1;
+                shift.sold += order.count();
+                shift.revenue += order.total();
+            }
+            _ => shifts.push(Shift {
+                name: name_of(order).to_owned(),
+                start: order.time,
+                end: order.time,
+                orders: 1,
+                sold: order.count(),
+                revenue: order.total(),
+            }),
+        }
+    }
+
+    shifts
+}
+
+/// 担当ごと・シフトごとの表を出す
+pub fn report_ui(ui: &mut egui::Ui, totals: &[StaffTotal], shifts: &[Shift]) {
+    ui.label("担当ごと");
+    egui::Grid::new("staff_totals_grid")
+        .num_columns(4)
+        .striped(true)
+        .show(ui, |ui| {
+            for total in totals {
+                ui.label(&total.name);
+                ui.label(format!("{}件", total.orders));
+                ui.label(format!("{}玉", total.sold));
+                ui.label(format!("¥{}", total.revenue));
+                ui.end_row();
+            }
+        });
+
+    ui.separator();
+
+    ui.label("シフトごと");
+    egui::Grid::new("staff_shifts_grid")
+        .num_columns(5)
+        .striped(true)
+        .show(ui, |ui| {
+            for shift in shifts {
+                let local = |time: chrono::DateTime<chrono::Utc>| {
+                    time.with_timezone(&chrono::Local).format("%H:%M")
+                };
+                ui.label(format!("{}〜{}", local(shift.start), local(shift.end)));
+                ui.label(&shift.name);
+                ui.label(format!("{}件", shift.orders));
+                ui.label(format!("{}玉", shift.sold));
+                ui.label(format!("¥{}", shift.revenue));
+                ui.end_row();
+            }
+        });
+}
+
+/// 担当の交代ウィンドウで押されたボタン
+pub enum LoginAction {
+    None,
+    // 選んだ人に交代する
+    Login(String),
+    // 担当なしにする
+    Logout,
+    // 担当の一覧を変えた（操作の記録に残す説明）
+    Edit(String),
+}
+
+/// 担当の交代ウィンドウの入力
+#[derive(Default)]
+pub struct LoginForm {
+    pub name: String,
+    pub pin: String,
+    // 暗証番号が違った
+    wrong_pin: bool,
+    // 担当の一覧を変えるための管理者の暗証番号
+    admin_pin: String,
+    wrong_admin_pin: bool,
+    // 管理者の暗証番号を入れて、一覧を変えられる
+    unlocked: bool,
+    // 一覧に足す人
+    new_name: String,
+    new_pin: String,
+}
+
+impl LoginForm {
+    pub fn new(current: &str) -> Self {
+        Self {
+            name: current.to_owned(),
+            ..Self::default()
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, settings: &mut Settings) -> LoginAction {
+        let mut action = LoginAction::None;
+
+        if settings.staff.is_empty() {
+            ui.label("下の「担当の一覧」に名前を足してください");
+        } else {
+            egui::ComboBox::from_label("名前")
+                .selected_text(&self.name)
+                .show_ui(ui, |ui| {
+                    for member in &settings.staff {
+                        ui.selectable_value(&mut self.name, member.name.clone(), &member.name);
+                    }
+                });
+
+            let member = settings.staff_member(&self.name);
+            if member.is_some_and(|member| member.pin.is_some()) {
+                ui.horizontal(|ui| {
+                    ui.label("暗証番号");
+                    ui.add(egui::TextEdit::singleline(&mut self.pin).password(true));
+                });
+            }
+            if self.wrong_pin {
+                ui.colored_label(egui::Color32::RED, "暗証番号が違います");
+            }
+
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(member.is_some(), egui::Button::new("交代する"))
+                    .clicked()
+                {
+                    let matches = member.is_some_and(|member| {
+                        member
+                            .pin
+                            .as_ref()
+                            .map_or(true, |pin| pin.matches(&self.pin))
+                    });
+                    if matches {
+                        action = LoginAction::Login(self.name.clone());
+                    } else {
+                        self.wrong_pin = true;
+                        self.pin.clear();
+                    }
+                }
+                if ui.button("担当なしにする").clicked() {
+                    action = LoginAction::Logout;
+                }
+            });
+        }
+
+        ui.separator();
+
+        ui.collapsing("担当の一覧", |ui| {
+            if let Some(edit) = self.staff_list_ui(ui, settings) {
+                action = LoginAction::Edit(edit);
+            }
+        });
+
+        action
+    }
+
+    // 管理者の暗証番号を入れてから、一覧を変える
+    // 変えたら、その説明を返す
+    fn staff_list_ui(&mut self, ui: &mut egui::Ui, settings: &mut Settings) -> Option<String> {
+        if settings.admin_pin.is_none() {
+            ui.label("一覧を変える前に、管理者の暗証番号を決めてください");
+            let mut edit = None;
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut self.admin_pin).password(true));
+                let enabled = !self.admin_pin.is_empty();
+                if ui
+                    .add_enabled(enabled, egui::Button::new("決める"))
+                    .clicked()
+                {
+                    settings.admin_pin = Some(PinHash::new(&std::mem::take(&mut self.admin_pin)));
+                    self.unlocked = true;
+                    edit = Some("管理者の暗証番号を決めた".to_owned());
+                }
+            });
+            return edit;
+        }
+
+        if !self.unlocked {
+            ui.horizontal(|ui| {
+                ui.label("管理者の暗証番号");
+                ui.add(egui::TextEdit::singleline(&mut self.admin_pin).password(true));
+                if ui.button("開く").clicked() {
+                    self.unlocked = settings
+                        .admin_pin
+                        .as_ref()
+                        .is_some_and(|pin| pin.matches(&self.admin_pin));
+                    self.wrong_admin_pin = !self.unlocked;
+                    self.admin_pin.clear();
+                }
+            });
+            if self.wrong_admin_pin {
+                ui.colored_label(egui::Color32::RED, "暗証番号が違います");
+            }
+            return None;
+        }
+
+        let staff = &mut settings.staff;
+        let mut edit = None;
+
+        let mut remove = None;
+        egui::Grid::new("staff_list_grid")
+            .num_columns(3)
+            .show(ui, |ui| {
+                for (i, member) in staff.iter().enumerate() {
+                    ui.label(&member.name);
+                    ui.label(if member.pin.is_some() {
+                        "暗証番号あり"
+                    } else {
+                        "暗証番号なし"
+                    });
+                    if ui.small_button("削除").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(i) = remove {
+            let member = staff.remove(i);
+            edit = Some(format!("{}を削除", member.name));
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("名前");
+            ui.add(egui::TextEdit::singleline(&mut self.new_name).desired_width(80.0));
+            ui.label("暗証番号（任意）");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.new_pin)
+                    .password(true)
+                    .desired_width(60.0),
+            );
+
+            let name = self.new_name.trim();
+            let addable = !name.is_empty() && !staff.iter().any(|member| member.name == name);
+            if ui.add_enabled(addable, egui::Button::new("追加")).clicked() {
+                let pin = std::mem::take(&mut self.new_pin);
+                staff.push(StaffMember {
+                    name: name.to_owned(),
+                    pin: (!pin.is_empty()).then(|| PinHash::new(&pin)),
+                });
+                edit = Some(format!("{}を追加", name));
+                self.new_name.clear();
+            }
+        });
+
+        edit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(time: &str, staff: Option<&str>, n: usize) -> FoodOrder {
+        let mut order = crate::test_util::plain(time, n);
+        order.staff = staff.map(str::to_owned);
+        order
+    }
+
+    #[test]
+    fn sums_orders_per_staff() {
+        let orders = [
+            order("2024-11-02T01:00:00Z", Some("佐藤"), 2),
+            order("2024-11-02T01:05:00Z", Some("鈴木"), 1),
+            order("2024-11-02T01:10:00Z", Some("佐藤"), 3),
+            order("2024-11-02T01:15:00Z", None, 1),
+        ];
+
+        let totals = totals(orders.iter());
+
+        let rows: Vec<(&str, usize, usize, u32)> = totals
+            .iter()
+            .map(|total| (total.name.as_str(), total.orders, total.sold, total.revenue))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("佐藤", 2, 5, 500),
+                ("鈴木", 1, 1, 100),
+                (NO_STAFF, 1, 1, 100),
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_shifts_where_staff_changes() {
+        // 並びが前後していても時刻順に区切る
+        let orders = [
+            order("2024-11-02T01:10:00Z", Some("鈴木"), 1),
+            order("2024-11-02T01:00:00Z", Some("佐藤"), 2),
+            order("2024-11-02T01:05:00Z", Some("佐藤"), 1),
+            order("2024-11-02T01:20:00Z", Some("佐藤"), 3),
+        ];
+
+        let shifts = shifts(orders.iter());
+
+        let rows: Vec<(&str, String, String, usize, usize)> = shifts
+            .iter()
+            .map(|shift| {
+                (
+                    shift.name.as_str(),
+                    shift.start.format("%H:%M").to_string(),
+                    shift.end.format("%H:%M").to_string(),
+                    shift.orders,
+                    shift.sold,
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("佐藤", "01:00".to_owned(), "01:05".to_owned(), 2, 3),
+                ("鈴木", "01:10".to_owned(), "01:10".to_owned(), 1, 1),
+                ("佐藤", "01:20".to_owned(), "01:20".to_owned(), 1, 3),
+            ]
+        );
+        assert_eq!(shifts[0].revenue, 300);
+    }
+}
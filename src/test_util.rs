@@ -0,0 +1,37 @@
+//! テストで使う記録と一時フォルダの作り方
+
+use crate::order::{FoodOrder, Order, OrderLine, SoldFood};
+
+/// `time` に受けた、プレーン `n` 玉（1玉100円）の注文
+pub fn plain(time: &str, n: usize) -> FoodOrder {
+    let time = time.parse().unwrap();
+    let line = OrderLine {
+        food: SoldFood {
+            id: 1,
+            name: "プレーン".to_owned(),
+            time,
+        },
+        n,
+        price: n as u32 * 100,
+    };
+    FoodOrder::new(time, vec![line])
+}
+
+/// `time` に受けた、プレーン3玉（300円）の注文
+pub fn order(time: &str) -> FoodOrder {
+    plain(time, 3)
+}
+
+/// `history` に入れる `order`
+pub fn food(time: &str) -> Order {
+    Order::Food(order(time))
+}
+
+/// テストごとの空の一時フォルダ
+pub fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("food_counter_test_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
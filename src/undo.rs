@@ -0,0 +1,157 @@
+//! 操作の取り消しとやり直し
+
+use crate::menu::Menu;
+use crate::order::Order;
+
+/// 取り消し・やり直しのできる操作1回分
+pub enum Command {
+    // `history` に1件足した（注文・レジ締め・セッションの開始）
+    Push(Order),
+    // `history` に続けて何件か足した（レジ締めと次のセッションの開始）
+    PushAll(Vec<Order>),
+    // 注文個数を変えた
+    Quantity { before: usize, after: usize },
+    // メニューを変えた
+    Menu { before: Menu, after: Menu },
+}
+
+/// 取り消した操作はやり直し用に移し、新しい操作をしたらやり直し用は捨てる
+#[derive(Default)]
+pub struct UndoStack {
+    undo: Vec<Command>,
+    redo: Vec<Command>,
+}
+
+impl UndoStack {
+    /// 新しい操作を積む
+    pub fn push(&mut self, command: Command) {
+        self.redo.clear();
+
+        // 続けて個数を変えたときは1回にまとめる
+        // 元の個数に戻ったら、取り消すものは無い
+        if let (
+            Command::Quantity { after, .. },
+            Some(Command::Quantity {
+                before,
+                after: last,
+            }),
+        ) = (&command, self.undo.last_mut())
+        {
+            if *after == *before {
+                self.undo.pop();
+            } else {
+                *last = *after;
+            }
+            return;
+        }
+
+        self.undo.push(command);
+    }
+
+    pub fn take_undo(&mut self) -> Option<Command> {
+        self.undo.pop()
+    }
+
+    pub fn take_redo(&mut self) -> Option<Command> {
+        self.redo.pop()
+    }
+
+    /// 取り消した操作を、やり直せるように残す
+    pub fn undone(&mut self, command: Command) {
+        self.redo.push(command);
+    }
+
+    /// やり直した操作を、また取り消せるように残す
+    pub fn redone(&mut self, command: Command) {
+        self.undo.push(command);
+    }
+
+    pub fn last_undo(&self) -> Option<&Command> {
+        self.undo.last()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quantity(command: Option<&Command>) -> Option<(usize, usize)> {
+        match command {
+            Some(Command::Quantity { before, after }) => Some((*before, *after)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn undoes_and_redoes_in_order() {
+        let mut stack = UndoStack::default();
+        stack.push(Command::Menu {
+            before: Menu::default(),
+            after: Menu { items: vec![] },
+        });
+        stack.push(Command::Quantity {
+            before: 1,
+            after: 2,
+        });
+
+        let command = stack.take_undo().unwrap();
+        assert_eq!(quantity(Some(&command)), Some((1, 2)));
+        stack.undone(command);
+        assert!(stack.can_redo());
+        assert!(matches!(stack.last_undo(), Some(Command::Menu { .. })));
+
+        let command = stack.take_redo().unwrap();
+        stack.redone(command);
+        assert!(!stack.can_redo());
+        assert_eq!(quantity(stack.last_undo()), Some((1, 2)));
+    }
+
+    #[test]
+    fn merges_quantity_changes() {
+        let mut stack = UndoStack::default();
+        stack.push(Command::Quantity {
+            before: 1,
+            after: 2,
+        });
+        stack.push(Command::Quantity {
+            before: 2,
+            after: 3,
+        });
+        assert_eq!(quantity(stack.last_undo()), Some((1, 3)));
+
+        // 元の個数に戻したら、操作は残さない
+        stack.push(Command::Quantity {
+            before: 3,
+            after: 1,
+        });
+        assert!(stack.last_undo().is_none());
+    }
+
+    #[test]
+    fn new_command_clears_redo() {
+        let mut stack = UndoStack::default();
+        stack.push(Command::Quantity {
+            before: 1,
+            after: 2,
+        });
+        let command = stack.take_undo().unwrap();
+        stack.undone(command);
+        assert!(stack.can_redo());
+
+        stack.push(Command::Menu {
+            before: Menu::default(),
+            after: Menu { items: vec![] },
+        });
+        assert!(!stack.can_redo());
+        assert!(stack.take_redo().is_none());
+    }
+}
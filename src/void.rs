@@ -0,0 +1,46 @@
+/// 注文を無効にした記録
+/// 無効にした注文も `history` に残し、個数や売上には数えない
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct Void {
+    pub time: chrono::DateTime<chrono::Utc>,
+    pub reason: String,
+}
+
+/// 注文を無効にするウィンドウの入力
+pub struct VoidForm {
+    // 無効にする注文の `history` 上の位置
+    pub order: usize,
+    // 注文の中身（確認用）
+    pub summary: String,
+    pub reason: String,
+}
+
+impl VoidForm {
+    pub fn new(order: usize, summary: String) -> Self {
+        Self {
+            order,
+            summary,
+            reason: String::new(),
+        }
+    }
+
+    /// 無効にするボタンが押されたら true
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        ui.label(&self.summary);
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.label("理由");
+            ui.text_edit_singleline(&mut self.reason);
+        });
+
+        ui.separator();
+
+        ui.add_enabled(
+            !self.reason.trim().is_empty(),
+            egui::Button::new("無効にする"),
+        )
+        .clicked()
+    }
+}